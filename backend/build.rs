@@ -30,6 +30,9 @@ fn main() {
 
 
 const DEFAULT_SAMPLE: &'static str = r#"
+# Every key can also be set with an environment variable named like the key in upper case and
+# prefixed with `SHORTY_`, e.g. `SHORTY_PUBLIC_URL`, or on the command line with `--set public_url=...`.
+# Environment variables take precedence over this file, and the command line over both.
 # The URL where the server should bind to
 # Optional; default is '127.0.0.1'.
 # listen_url = _LISTEN_URL_DEFAULT
@@ -42,10 +45,12 @@ const DEFAULT_SAMPLE: &'static str = r#"
 # The shortened link ajnIUh1H in the server response will look like `$public_url/ajnIUh1H`.
 # If the public url is `short.example.com` the link the server will respond with will be `http://shorty.example.com/ajnIUh1H`.
 # It is different from the listen_url if shorty is run behind a reverse proxy.
-public_url = 'http://localhost:7999'
+# Optional; default is 'http://localhost:7999'.
+public_url = _PUBLIC_URL_DEFAULT
 
 # Where the server should look for the database
-database_location = 'database.db'
+# Optional; default is 'database.db'.
+database_location = _DATABASE_LOCATION_DEFAULT
 
 # The maximum length a link may have.
 # Optional; default is 500 chars length.
@@ -59,6 +64,72 @@ database_location = 'database.db'
 # Optional; default is 2500
 # max_custom_id_length = _MAX_CUSTOM_ID_LENGTH_DEFAULT
 
+# The maximum number of links that can be created at once with `POST /custom/batch`.
+# The whole batch has to fit into max_json_size as well.
+# Optional; default is _MAX_BATCH_SIZE_DEFAULT.
+# max_batch_size = _MAX_BATCH_SIZE_DEFAULT
+
+# The maximum number of links or reports the admin API lists at once, larger `limit`s are lowered to this.
+# Optional; default is _MAX_PAGE_SIZE_DEFAULT.
+# max_page_size = _MAX_PAGE_SIZE_DEFAULT
+
+
+# The schemes links may redirect to. Links with any other scheme, like `javascript:` or `ftp:`, are rejected.
+# Optional; default is ['http', 'https'].
+# allowed_target_schemes = ['http', 'https']
+
+# The scheme links that are shortened without one get.
+# Optional; default is 'https'.
+# default_target_scheme = _DEFAULT_TARGET_SCHEME_DEFAULT
+
+# Which domains links may redirect to. `example.com` only matches the domain itself, `*.example.com` all of its subdomains.
+# Blocked domains always take precedence. If any domain is allowed, all other domains are blocked.
+# Links to domains that get blocked later stop working immediately.
+# Optional; by default every domain is allowed.
+# allowed_domains = ['example.com', '*.example.com']
+# blocked_domains = ['phishing.example']
+
+# Files with more domains to allow or block, one per line. Empty lines and lines starting with '#' are ignored.
+# They are reloaded whenever they change, just like this config.
+# Optional.
+# allowed_domains_file = 'allowed-domains.txt'
+# blocked_domains_file = 'blocked-domains.txt'
+
+# After how many reports from different addresses a link gets disabled until an admin reviews it.
+# 0 never disables links automatically.
+# Optional; default is _REPORTS_TO_DISABLE_DEFAULT.
+# reports_to_disable = _REPORTS_TO_DISABLE_DEFAULT
+
+# The addresses of reverse proxies in front of shorty. Only requests from them may tell who sent them with
+# `X-Forwarded-For`, otherwise anyone could pretend to be someone else, e.g. to report a link many times.
+# Optional; by default the header is ignored.
+# trusted_proxies = ['127.0.0.1', '::1']
+
+# A token for the admin API with the admin role, sent as `Authorization: Bearer <token>`. Use a long random string.
+# More tokens with restricted roles can be added as `[[api_tokens]]` at the end of this file.
+# The admin API is unavailable if there are no tokens.
+# Optional.
+# admin_token = ''
+
+# How IDs are compared. IDs that are the same after these normalizations are considered the same ID,
+# so a link can be opened with any of them and a new custom ID that is the same as an existing one is rejected.
+# The IDs themselves are stored and shown the way they were created.
+# Changing these normalizes the IDs of existing links again.
+# Shorty refuses changes that would make the IDs of two existing links or aliases the same.
+
+# Upper and lower case don't matter, `Docs` and `docs` are the same ID.
+# Optional; default is false.
+# case_insensitive_ids = true
+
+# IDs are compared after Unicode NFKC normalization, e.g. `ｄｏｃｓ` and `docs` are the same ID.
+# Optional; default is false.
+# normalize_unicode_ids = true
+
+# Characters that look alike are the same, e.g. `d0cs` and `dOcs`, or `Ink` and `lnk`.
+# Together with `case_insensitive_ids` `d0cs` and `docs` are the same ID as well.
+# Optional; default is false.
+# fold_confusable_ids = true
+
 
 # The link defaults that get used if they aren't specified.
 
@@ -70,7 +141,55 @@ database_location = 'database.db'
 # Optional, default is 7 days.
 # default_valid_for = _VALID_FOR_DURATION_DEFAULT # 24 hours
 
+# The status code links redirect with, 301, 302, 307 or 308. Can be set per link as well.
+# Browsers may cache 301 and 308 redirects of links without a use or time limit for a day,
+# redirects of all other links are never cached, so every use is counted.
+# Optional, default is _REDIRECT_TYPE_DEFAULT.
+# default_redirect_type = _REDIRECT_TYPE_DEFAULT
+
+# Query parameters added to the target of every link that doesn't have its own `append_query`,
+# unless the target has them already.
+# Optional; by default nothing is added.
+# default_append_query = { utm_source = 'shorty', utm_medium = 'link' }
+
+# Whether shortening a link that was shortened before returns the existing link instead of a new random ID.
+# Only active links created without a custom ID, a use or time limit and any other settings are shared,
+# so this has no effect unless default_max_uses and default_valid_for are 0. Can be set per request as well.
+# Optional; default is false.
+# deduplicate_links = true
+
+# How long the ID of an expired link can't be used for a new link, in milliseconds.
+# This way a link doesn't suddenly lead somewhere else for people who still have it.
+# Optional, default is 7 days.
+# expired_id_grace_period = _EXPIRED_ID_GRACE_PERIOD_DEFAULT
+
 # Location of custom frontend.
 # If set, files in the folder will be served instead of the embedded frontend.
+# `pages/link-error.html` in it replaces the page shown for links that can't be opened.
 # frontend_location = '/var/www/shorty_frontend'
+
+# How long to wait for in-flight requests to finish when shutting down, in seconds.
+# Connections still open after this are dropped.
+# Optional; default is 30 seconds.
+# shutdown_timeout = _SHUTDOWN_TIMEOUT_DEFAULT
+
+# Additional domains links can be shortened on. Every domain has its own IDs, so the same ID can
+# point somewhere different on each of them. The domain is picked by the host the request was sent to,
+# requests for any other host use the `public_url` above.
+# `host` defaults to the host of the domain's `public_url`, the link defaults fall back to the ones above.
+# This has to be at the end of the file, keys after a `[[domains]]` line belong to that domain.
+# [[domains]]
+# public_url = 'https://go.example.com'
+# host = 'go.example.com'
+# default_max_uses = 0
+# default_valid_for = 0
+# default_append_query = { utm_source = 'go' }
+
+# Tokens for the admin API, each with a role. `viewer` can list links and reports and view the totals,
+# `moderator` can also disable, reinstate and ban links and `admin` can also delete them.
+# The name shows up in the logs. Like `[[domains]]`, this has to be at the end of the file.
+# [[api_tokens]]
+# name = 'moderation-team'
+# token = ''
+# role = 'moderator'
 "#;
\ No newline at end of file
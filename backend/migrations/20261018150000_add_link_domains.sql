@@ -0,0 +1,22 @@
+-- Links are identified by the domain they were shortened on together with their ID.
+-- Existing links belong to the default domain, which is the empty string.
+create table links_with_domain
+(
+    domain      TEXT    not null default '',
+    id          TEXT    not null,
+    redirect_to TEXT    not null,
+    max_uses    integer not null,
+    invocations integer not null,
+    created_at  integer not null,
+    valid_for   integer not null,
+    constraint links_pk
+        primary key (domain, id)
+);
+
+INSERT INTO links_with_domain (domain, id, redirect_to, max_uses, invocations, created_at, valid_for)
+SELECT '', id, redirect_to, max_uses, invocations, created_at, valid_for
+FROM links;
+
+DROP TABLE links;
+
+ALTER TABLE links_with_domain RENAME TO links;
@@ -0,0 +1,7 @@
+-- The normalized ID is what lookups compare against, see the `*_ids` config options.
+-- Existing IDs were created without normalization.
+ALTER TABLE links ADD COLUMN normalized_id TEXT not null default '';
+
+UPDATE links SET normalized_id = id;
+
+CREATE INDEX links_normalized_id_idx ON links (domain, normalized_id);
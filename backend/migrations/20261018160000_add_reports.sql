@@ -0,0 +1,30 @@
+-- Why a link was disabled: 'reported' if it got enough reports, 'banned' if an admin banned it.
+-- Links that aren't disabled have no reason.
+ALTER TABLE links ADD COLUMN disabled_reason TEXT;
+
+-- Reports of malicious links by the public. A reporter can only report a link once.
+create table reports
+(
+    id          integer not null
+        constraint reports_pk
+            primary key autoincrement,
+    domain      TEXT    not null,
+    link_id     TEXT    not null,
+    reason      TEXT    not null,
+    reporter    TEXT    not null,
+    created_at  integer not null,
+    resolved_at integer,
+    constraint reports_reporter_uq
+        unique (domain, link_id, reporter)
+);
+
+CREATE INDEX reports_link_idx ON reports (domain, link_id);
+
+-- Hosts no link may redirect to anymore, because a link to them got banned.
+create table banned_domains
+(
+    host      TEXT    not null
+        constraint banned_domains_pk
+            primary key,
+    banned_at integer not null
+);
@@ -0,0 +1,26 @@
+-- Links are no longer deleted, their status records what happened to them instead:
+-- 'active', 'disabled' (see disabled_reason), 'expired' or 'deleted'.
+ALTER TABLE links ADD COLUMN status TEXT not null default 'active';
+-- When the status last changed, in milliseconds. For expired links this is when they expired.
+ALTER TABLE links ADD COLUMN status_changed_at integer;
+
+UPDATE links SET status = 'disabled', status_changed_at = created_at WHERE disabled_reason IS NOT NULL;
+
+-- Links whose ID was taken over by a new link, so it is known what an ID pointed to before.
+create table link_history
+(
+    domain            TEXT    not null,
+    id                TEXT    not null,
+    normalized_id     TEXT    not null,
+    redirect_to       TEXT    not null,
+    max_uses          integer not null,
+    invocations       integer not null,
+    created_at        integer not null,
+    valid_for         integer not null,
+    disabled_reason   TEXT,
+    status            TEXT    not null,
+    status_changed_at integer,
+    replaced_at       integer not null
+);
+
+CREATE INDEX link_history_id_idx ON link_history (domain, id);
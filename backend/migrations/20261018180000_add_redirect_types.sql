@@ -0,0 +1,3 @@
+-- The status code a link redirects with: 301, 302, 307 or 308.
+ALTER TABLE links ADD COLUMN redirect_type integer not null default 307;
+ALTER TABLE link_history ADD COLUMN redirect_type integer not null default 307;
@@ -0,0 +1,3 @@
+-- Prefix links also match longer paths and pass the rest of the path and the query on to their target.
+ALTER TABLE links ADD COLUMN prefix boolean not null default false;
+ALTER TABLE link_history ADD COLUMN prefix boolean not null default false;
@@ -0,0 +1,3 @@
+-- Template links fill placeholders in their target with the path segments and query parameters of the request.
+ALTER TABLE links ADD COLUMN template boolean not null default false;
+ALTER TABLE link_history ADD COLUMN template boolean not null default false;
@@ -0,0 +1,4 @@
+-- Query parameters added to the target on every redirect, form-urlencoded.
+-- NULL uses the defaults of the domain, an empty string adds nothing.
+ALTER TABLE links ADD COLUMN append_query text;
+ALTER TABLE link_history ADD COLUMN append_query text;
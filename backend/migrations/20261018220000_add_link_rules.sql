@@ -0,0 +1,3 @@
+-- Rules sending some visitors somewhere else than `redirect_to`, as a JSON array.
+ALTER TABLE links ADD COLUMN rules text;
+ALTER TABLE link_history ADD COLUMN rules text;
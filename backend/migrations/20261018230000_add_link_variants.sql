@@ -0,0 +1,18 @@
+-- Links splitting their visitors between several targets by weight.
+-- The link's own target is the variant at position 0.
+create table link_variants
+(
+    domain      TEXT    not null,
+    link_id     TEXT    not null,
+    position    integer not null,
+    redirect_to TEXT    not null,
+    weight      integer not null,
+    invocations integer not null default 0,
+    constraint link_variants_pk
+        primary key (domain, link_id, position)
+);
+
+ALTER TABLE links ADD COLUMN variant_count integer not null default 0;
+ALTER TABLE links ADD COLUMN sticky boolean not null default false;
+ALTER TABLE link_history ADD COLUMN variant_count integer not null default 0;
+ALTER TABLE link_history ADD COLUMN sticky boolean not null default false;
@@ -0,0 +1,6 @@
+-- When a link goes live, in milliseconds. Before that it doesn't redirect, or only to `not_before_redirect_to`.
+-- Links without one are live from their creation on. `valid_for` counts from whichever is later.
+ALTER TABLE links ADD COLUMN not_before integer;
+ALTER TABLE links ADD COLUMN not_before_redirect_to text;
+ALTER TABLE link_history ADD COLUMN not_before integer;
+ALTER TABLE link_history ADD COLUMN not_before_redirect_to text;
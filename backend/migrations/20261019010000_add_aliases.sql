@@ -0,0 +1,14 @@
+-- Further IDs leading to a link, which share its uses, expiry and stats.
+create table aliases
+(
+    domain        TEXT    not null,
+    id            TEXT    not null,
+    normalized_id TEXT    not null,
+    link_id       TEXT    not null,
+    created_at    integer not null,
+    constraint aliases_pk
+        primary key (domain, id)
+);
+
+CREATE INDEX aliases_normalized_id_idx ON aliases (domain, normalized_id);
+CREATE INDEX aliases_link_idx ON aliases (domain, link_id);
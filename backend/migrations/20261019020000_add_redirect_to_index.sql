@@ -0,0 +1,2 @@
+-- Finds existing links to a target, so shortening it again can return one of them.
+CREATE INDEX links_redirect_to_idx ON links (domain, redirect_to);
@@ -0,0 +1,26 @@
+-- Aliases are deleted together with the link they lead to, so none is left behind to shadow a new link.
+-- SQLite can't add constraints to existing tables, so the table is created again.
+create table aliases_with_link
+(
+    domain        TEXT    not null,
+    id            TEXT    not null,
+    normalized_id TEXT    not null,
+    link_id       TEXT    not null,
+    created_at    integer not null,
+    constraint aliases_pk
+        primary key (domain, id),
+    constraint aliases_link_fk
+        foreign key (domain, link_id) references links (domain, id)
+            on delete cascade
+);
+
+INSERT INTO aliases_with_link (domain, id, normalized_id, link_id, created_at)
+SELECT domain, id, normalized_id, link_id, created_at
+FROM aliases
+WHERE EXISTS (SELECT 1 FROM links WHERE links.domain = aliases.domain AND links.id = aliases.link_id);
+
+DROP TABLE aliases;
+ALTER TABLE aliases_with_link RENAME TO aliases;
+
+CREATE INDEX aliases_normalized_id_idx ON aliases (domain, normalized_id);
+CREATE INDEX aliases_link_idx ON aliases (domain, link_id);
@@ -0,0 +1,4 @@
+-- Whether the link can be returned when its target is shortened again, see `deduplicate_links`.
+-- Links are shareable if they were created without a custom ID, limits or any other settings.
+-- Whether existing links have a custom ID isn't known, so none of them are shared.
+ALTER TABLE links ADD COLUMN shareable BOOLEAN not null default false;
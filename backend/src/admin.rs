@@ -0,0 +1,685 @@
+use std::future::{Ready, ready};
+
+use actix_web::{delete, FromRequest, get, HttpRequest, HttpResponse, post, Responder, web};
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::CONFIG;
+use crate::alias::AliasRequest;
+use crate::config::Role;
+use crate::domain::Domain;
+use crate::importers::{self, Source};
+use crate::error::ShortyError;
+use crate::link::{Link, LinkFilter, LinkState, LinkStore};
+use crate::rules::Rule;
+use crate::transfer::{ConflictPolicy, Format, ImportOutcome, parse};
+
+/// Who sent a request to the admin API, identified by the bearer token they sent.
+/// The token is either the `admin_token` or one of the `api_tokens`.
+/// Requests without a known token are rejected with 401, as are all requests if no token is configured.
+#[derive(Debug)]
+pub struct Admin {
+	/// The name of the token, `admin` for the `admin_token`.
+	pub name: String,
+	pub role: Role,
+}
+
+impl Admin {
+	/// Checks that the token has at least the role.
+	///
+	/// # Errors
+	///
+	/// Errors with [`ShortyError::MissingRole`] if it doesn't.
+	pub fn require(&self, role: Role) -> Result<(), ShortyError> {
+		if self.role < role {
+			return Err(ShortyError::MissingRole(role));
+		}
+
+
+		Ok(())
+	}
+}
+
+impl FromRequest for Admin {
+	type Error = ShortyError;
+	type Future = Ready<Result<Self, Self::Error>>;
+
+	fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+		let Some(token) = req.headers()
+			.get(header::AUTHORIZATION)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.strip_prefix("Bearer "))
+			.map(str::trim) else {
+			return ready(Err(ShortyError::Unauthorized));
+		};
+
+		let config = CONFIG.load();
+		let admin_token = config.admin_token.iter()
+			.map(|admin_token| ("admin", admin_token.as_str(), Role::Admin));
+		let api_tokens = config.api_tokens.iter()
+			.map(|api_token| (api_token.name.as_str(), api_token.token.as_str(), api_token.role));
+
+		// Every token is compared, so the time it takes doesn't reveal which one matched.
+		let admin = admin_token.chain(api_tokens)
+			.fold(None, |found, (name, known, role)| {
+				let matches = constant_time_eq(token.as_bytes(), known.as_bytes());
+				found.or(matches.then(|| Admin { name: name.to_owned(), role }))
+			});
+
+
+		ready(admin.ok_or(ShortyError::Unauthorized))
+	}
+}
+
+/// Compares without returning early, so the time it takes doesn't reveal how much of the token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Selects the domain a link is on.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DomainQuery {
+	/// The host of the domain the link is on. Defaults to the default domain.
+	domain: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct Pagination {
+	/// How many entries to return.
+	#[serde(default = "limit_default")]
+	limit: i64,
+	/// How many entries to skip.
+	#[serde(default)]
+	offset: i64,
+}
+
+const fn limit_default() -> i64 { 100 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ReportsQuery {
+	/// Whether reports that were already reviewed should be included.
+	#[serde(default)]
+	resolved: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BanQuery {
+	/// The host of the domain the link is on. Defaults to the default domain.
+	domain: Option<String>,
+	/// Whether the host the link redirects to should be banned as well, so no link can redirect to it anymore.
+	#[serde(default)]
+	ban_target: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportQuery {
+	/// `jsonl` for JSON Lines or `csv`. Defaults to `jsonl`.
+	#[serde(default)]
+	#[param(inline)]
+	format: Format,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ImportQuery {
+	/// `jsonl` for JSON Lines or `csv`. Defaults to `jsonl`.
+	#[serde(default)]
+	#[param(inline)]
+	format: Format,
+	/// What happens to links whose ID is taken: `skip` them, `overwrite` what has the ID or `rename` them to a random ID.
+	/// Defaults to `skip`.
+	#[serde(default)]
+	#[param(inline)]
+	on_conflict: ConflictPolicy,
+	/// The link shortener the input was exported from, `yourls`, `kutt` or `bitly`, if it isn't an export of shorty.
+	/// `format` is ignored then.
+	#[param(inline)]
+	from: Option<Source>,
+	/// The host of the domain links from other link shorteners are put on. Defaults to the default domain.
+	domain: Option<String>,
+	/// Whether the import should only report what it would do, e.g. which IDs are taken, without importing anything.
+	#[serde(default)]
+	dry_run: bool,
+}
+
+/// A link as the admin API shows it.
+/// All timestamps are in milliseconds.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminLink {
+	/// The host of the domain the link is on, empty for the default domain.
+	domain: String,
+	id: String,
+	/// The shortened URL.
+	url: String,
+	redirect_to: String,
+	max_uses: i64,
+	invocations: i64,
+	created_at: i64,
+	valid_for: i64,
+	/// The status code the link redirects with.
+	redirect_type: i64,
+	/// Whether the link passes longer paths and the query on to its target.
+	prefix: bool,
+	/// Whether the target is a template with placeholders.
+	template: bool,
+	/// The form-urlencoded query parameters added to the target, the defaults of the domain are used if this is missing.
+	append_query: Option<String>,
+	/// Rules sending some visitors somewhere else, in the order they are matched in.
+	rules: Vec<Rule>,
+	/// How many variants the link splits its visitors between, including its own target. 0 if it has none.
+	variant_count: i64,
+	/// Whether visitors keep getting the same variant.
+	sticky: bool,
+	/// When the link goes live, if it wasn't live right away.
+	not_before: Option<i64>,
+	/// Where the link redirects to before it goes live.
+	not_before_redirect_to: Option<String>,
+	expires_at: Option<i64>,
+	state: LinkState,
+	/// When the state last changed. For expired links this is when they expired, if it is known yet.
+	status_changed_at: Option<i64>,
+	disabled_reason: Option<String>,
+}
+
+impl From<Link> for AdminLink {
+	fn from(link: Link) -> Self {
+		Self {
+			url: link.formatted(),
+			expires_at: link.expires_at(),
+			state: link.state(),
+			rules: link.rules(),
+			domain: link.domain,
+			id: link.id,
+			redirect_to: link.redirect_to,
+			max_uses: link.max_uses,
+			invocations: link.invocations,
+			created_at: link.created_at,
+			valid_for: link.valid_for,
+			redirect_type: link.redirect_type,
+			prefix: link.prefix,
+			template: link.template,
+			append_query: link.append_query,
+			variant_count: link.variant_count,
+			sticky: link.sticky,
+			not_before: link.not_before,
+			not_before_redirect_to: link.not_before_redirect_to,
+			status_changed_at: link.status_changed_at,
+			disabled_reason: link.disabled_reason,
+		}
+	}
+}
+
+/// A page of links.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LinkPage {
+	/// How many links match the filters in total.
+	total: i64,
+	links: Vec<AdminLink>,
+}
+
+/// List links
+///
+/// Lists the links matching the filters, newest first.
+#[utoipa::path(
+	tag = "/admin",
+	params(LinkFilter, Pagination),
+	responses(
+		(status = 200, body = LinkPage, description = "The links"),
+		(status = 400, description = "The domain isn't configured"),
+		(status = 401, description = "The token is missing or wrong"),
+	),
+	security(("api_token" = [])),
+)]
+#[get("/admin/links")]
+async fn list_links(
+	_: Admin,
+	filter: web::Query<LinkFilter>,
+	pagination: web::Query<Pagination>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	let (links, total) = link_store.search(&filter, pagination.limit, pagination.offset).await?;
+	let links = links.into_iter().map(AdminLink::from).collect();
+
+
+	Ok(HttpResponse::Ok().json(LinkPage { total, links }))
+}
+
+/// Link history
+///
+/// Lists what the ID pointed to before it was used for the current link, most recently replaced first.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		DomainQuery,
+	),
+	responses(
+		(status = 200, body = [AdminLink], description = "The links the ID pointed to before"),
+		(status = 401, description = "The token is missing or wrong"),
+	),
+	security(("api_token" = [])),
+)]
+#[get("/admin/links/{link_id}/history")]
+async fn get_link_history(
+	_: Admin,
+	params: web::Path<String>,
+	query: web::Query<DomainQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	let history: Vec<AdminLink> = link_store.history(&domain, link_id.as_str()).await?
+		.into_iter()
+		.map(AdminLink::from)
+		.collect();
+
+
+	Ok(HttpResponse::Ok().json(history))
+}
+
+/// Link variants
+///
+/// Lists the targets the link splits its visitors between, with how many visitors each one got.
+/// The link's own target is the variant at position 0. Links without variants have none.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		DomainQuery,
+	),
+	responses(
+		(status = 200, body = [Variant], description = "The variants of the link"),
+		(status = 401, description = "The token is missing or wrong"),
+	),
+	security(("api_token" = [])),
+)]
+#[get("/admin/links/{link_id}/variants")]
+async fn get_link_variants(
+	_: Admin,
+	params: web::Path<String>,
+	query: web::Query<DomainQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	let variants = link_store.variants(&domain, link_id.as_str()).await?;
+
+
+	Ok(HttpResponse::Ok().json(variants))
+}
+
+/// List aliases of a link
+///
+/// Lists the further IDs leading to a link, oldest first.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		DomainQuery,
+	),
+	responses(
+		(status = 200, body = [Alias], description = "The aliases of the link"),
+		(status = 401, description = "The token is missing or wrong"),
+	),
+	security(("api_token" = [])),
+)]
+#[get("/admin/links/{link_id}/aliases")]
+async fn get_link_aliases(
+	_: Admin,
+	params: web::Path<String>,
+	query: web::Query<DomainQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	let aliases = link_store.aliases(&domain, link_id.as_str()).await?;
+
+
+	Ok(HttpResponse::Ok().json(aliases))
+}
+
+/// Add an alias to a link
+///
+/// Makes a link reachable with a further ID. Visits through the alias count as uses of the link,
+/// so they share `max_uses`, expiry and stats. Requires the admin role.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		DomainQuery,
+	),
+	request_body(content = AliasRequest, description = "The ID of the alias", content_type = "application/json"),
+	responses(
+		(status = 201, body = Alias, description = "The alias was added"),
+		(status = 400, description = "The ID of the alias is too long"),
+		(status = 401, description = "The token is missing or wrong"),
+		(status = 403, description = "The token lacks the admin role"),
+		(status = 404, description = "There is no link with the id"),
+		(status = 409, description = "The ID of the alias is already taken"),
+	),
+	security(("api_token" = [])),
+)]
+#[post("/admin/links/{link_id}/aliases")]
+async fn add_link_alias(
+	admin: Admin,
+	params: web::Path<String>,
+	query: web::Query<DomainQuery>,
+	alias: web::Json<AliasRequest>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	admin.require(Role::Admin)?;
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	let Some(alias) = link_store.add_alias(&domain, link_id.as_str(), alias.id.as_str()).await? else {
+		return Ok(HttpResponse::NotFound().finish());
+	};
+	info!("{} added the alias {} to {}.", admin.name, alias.id, alias.link_id);
+
+
+	Ok(HttpResponse::Created().json(alias))
+}
+
+/// Remove an alias from a link
+///
+/// The ID of the alias can be used again right away. Requires the admin role.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		("alias" = inline(String), Path, description = "The id of the alias"),
+		DomainQuery,
+	),
+	responses(
+		(status = 204, description = "The alias was removed"),
+		(status = 401, description = "The token is missing or wrong"),
+		(status = 403, description = "The token lacks the admin role"),
+		(status = 404, description = "The link has no alias with the id"),
+	),
+	security(("api_token" = [])),
+)]
+#[delete("/admin/links/{link_id}/aliases/{alias}")]
+async fn remove_link_alias(
+	admin: Admin,
+	params: web::Path<(String, String)>,
+	query: web::Query<DomainQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	admin.require(Role::Admin)?;
+	let (link_id, alias) = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	if !link_store.remove_alias(&domain, link_id.as_str(), alias.as_str()).await? {
+		return Ok(HttpResponse::NotFound().finish());
+	}
+	info!("{} removed the alias {alias} from {link_id}.", admin.name);
+
+
+	Ok(HttpResponse::NoContent().finish())
+}
+
+/// Export links
+///
+/// Exports all links, whatever their state, as JSON Lines or CSV. Variants and aliases aren't included.
+#[utoipa::path(
+	tag = "/admin",
+	params(ExportQuery),
+	responses(
+		(status = 200, body = [ExportedLink], description = "The links, one per line"),
+		(status = 401, description = "The token is missing or wrong"),
+	),
+	security(("api_token" = [])),
+)]
+#[get("/admin/export")]
+async fn export_links(
+	admin: Admin,
+	query: web::Query<ExportQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	info!("{} exported the links.", admin.name);
+
+
+	Ok(
+		HttpResponse::Ok()
+			.content_type(query.format.content_type())
+			.insert_header(header::ContentDisposition::attachment(format!("shorty-links.{}", query.format.extension())))
+			.streaming(link_store.export(query.format))
+	)
+}
+
+/// Import links
+///
+/// Imports links exported by `/admin/export`, or with `from` by YOURLS, Kutt or Bitly.
+/// Every link is validated like links created through `/custom`, its uses, creation time and status are taken over.
+/// All links are imported in a single transaction, links that can't be imported don't keep the others from being imported.
+/// Requires the admin role.
+#[utoipa::path(
+	tag = "/admin",
+	params(ImportQuery),
+	request_body(content = String, description = "The links as JSON Lines or CSV, or in the export format of `from`", content_type = "text/plain"),
+	responses(
+		(status = 200, body = [ImportResult], description = "The outcome for every link, in the order of the input"),
+		(status = 401, description = "The token is missing or wrong"),
+		(status = 403, description = "The token lacks the admin role"),
+		(status = 413, description = "The input exceeds `max_json_size`"),
+	),
+	security(("api_token" = [])),
+)]
+#[post("/admin/import")]
+async fn import_links(
+	admin: Admin,
+	query: web::Query<ImportQuery>,
+	body: web::Bytes,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	admin.require(Role::Admin)?;
+	let links = match query.from {
+		Some(source) => {
+			let domain = Domain::requested_or_default(query.domain.as_deref())?;
+			importers::parse(source, &body, domain.name.as_str())
+		}
+		None => parse(query.format, &body),
+	};
+	let results = link_store.import(links, query.on_conflict, query.dry_run).await?;
+	if !query.dry_run {
+		let imported = results.iter()
+			.filter(|result| !matches!(result.outcome, ImportOutcome::Skipped | ImportOutcome::Failed))
+			.count();
+		info!("{} imported {imported} of {} links.", admin.name, results.len());
+	}
+
+
+	Ok(HttpResponse::Ok().json(results))
+}
+
+/// Instance totals
+///
+/// Counts the links by state, their uses, the open reports and the banned domains.
+#[utoipa::path(
+	tag = "/admin",
+	responses(
+		(status = 200, body = Totals, description = "The totals"),
+		(status = 401, description = "The token is missing or wrong"),
+	),
+	security(("api_token" = [])),
+)]
+#[get("/admin/totals")]
+async fn get_totals(
+	_: Admin,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	Ok(HttpResponse::Ok().json(link_store.totals().await?))
+}
+
+/// Disable a link
+///
+/// Disables a link until it is reinstated. Requires the moderator role.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		DomainQuery,
+	),
+	responses(
+		(status = 204, description = "The link was disabled"),
+		(status = 401, description = "The token is missing or wrong"),
+		(status = 403, description = "The token lacks the moderator role"),
+		(status = 404, description = "There is no link with the id"),
+		(status = 409, description = "The link is banned"),
+	),
+	security(("api_token" = [])),
+)]
+#[post("/admin/links/{link_id}/disable")]
+async fn disable_link(
+	admin: Admin,
+	params: web::Path<String>,
+	query: web::Query<DomainQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	admin.require(Role::Moderator)?;
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	if !link_store.disable(&domain, link_id.as_str()).await? {
+		return Ok(HttpResponse::NotFound().finish());
+	}
+	info!("{} disabled {link_id}.", admin.name);
+
+
+	Ok(HttpResponse::NoContent().finish())
+}
+
+/// Delete a link
+///
+/// Marks a link as deleted, its ID can be used again right away. Requires the admin role.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		DomainQuery,
+	),
+	responses(
+		(status = 204, description = "The link was deleted"),
+		(status = 401, description = "The token is missing or wrong"),
+		(status = 403, description = "The token lacks the admin role"),
+		(status = 404, description = "There is no link with the id"),
+	),
+	security(("api_token" = [])),
+)]
+#[delete("/admin/links/{link_id}")]
+async fn delete_link(
+	admin: Admin,
+	params: web::Path<String>,
+	query: web::Query<DomainQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	admin.require(Role::Admin)?;
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	if !link_store.delete(&domain, link_id.as_str()).await? {
+		return Ok(HttpResponse::NotFound().finish());
+	}
+	info!("{} deleted {link_id}.", admin.name);
+
+
+	Ok(HttpResponse::NoContent().finish())
+}
+
+/// List reports
+///
+/// Lists the reports of links, newest first.
+#[utoipa::path(
+	tag = "/admin",
+	params(ReportsQuery, Pagination),
+	responses(
+		(status = 200, body = [Report], description = "The reports"),
+		(status = 401, description = "The token is missing or wrong"),
+	),
+	security(("api_token" = [])),
+)]
+#[get("/admin/reports")]
+async fn list_reports(
+	_: Admin,
+	query: web::Query<ReportsQuery>,
+	pagination: web::Query<Pagination>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	let reports = link_store.reports(query.resolved, pagination.limit, pagination.offset).await?;
+
+
+	Ok(HttpResponse::Ok().json(reports))
+}
+
+/// Reinstate a link
+///
+/// Enables a link that was disabled again and resolves its reports. Banned links stay disabled.
+/// Requires the moderator role.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		DomainQuery,
+	),
+	responses(
+		(status = 204, description = "The link was reinstated"),
+		(status = 401, description = "The token is missing or wrong"),
+		(status = 403, description = "The token lacks the moderator role"),
+		(status = 404, description = "There is no link with the id"),
+		(status = 409, description = "The link is banned"),
+	),
+	security(("api_token" = [])),
+)]
+#[post("/admin/links/{link_id}/reinstate")]
+async fn reinstate_link(
+	admin: Admin,
+	params: web::Path<String>,
+	query: web::Query<DomainQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	admin.require(Role::Moderator)?;
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	if !link_store.reinstate(&domain, link_id.as_str()).await? {
+		return Ok(HttpResponse::NotFound().finish());
+	}
+	info!("{} reinstated {link_id}.", admin.name);
+
+
+	Ok(HttpResponse::NoContent().finish())
+}
+
+/// Ban a link
+///
+/// Permanently disables a link and resolves its reports, optionally banning the host it redirects to as well.
+/// Requires the moderator role.
+#[utoipa::path(
+	tag = "/admin",
+	params(
+		("link_id" = inline(String), Path, description = "The id of the link"),
+		BanQuery,
+	),
+	responses(
+		(status = 204, description = "The link was banned"),
+		(status = 401, description = "The token is missing or wrong"),
+		(status = 403, description = "The token lacks the moderator role"),
+		(status = 404, description = "There is no link with the id"),
+	),
+	security(("api_token" = [])),
+)]
+#[post("/admin/links/{link_id}/ban")]
+async fn ban_link(
+	admin: Admin,
+	params: web::Path<String>,
+	query: web::Query<BanQuery>,
+	link_store: web::Data<LinkStore>,
+) -> Result<impl Responder, ShortyError> {
+	admin.require(Role::Moderator)?;
+	let link_id = params.into_inner();
+	let domain = Domain::requested_or_default(query.domain.as_deref())?;
+	let Some(link) = link_store.ban(&domain, link_id.as_str(), query.ban_target).await? else {
+		return Ok(HttpResponse::NotFound().finish());
+	};
+	info!("{} banned {}, which redirected to {}.", admin.name, link.formatted(), link.redirect_to);
+
+
+	Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Pool, Sqlite, SqliteConnection};
+use utoipa::ToSchema;
+
+use crate::error::ShortyError;
+use crate::util::time_now;
+
+/// Another ID leading to a link. Visits through an alias count as uses of the link,
+/// so the link and all of its aliases share one `max_uses` budget, one expiry and one set of stats.
+/// All timestamps are in milliseconds.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Alias {
+	#[serde(skip)]
+	pub domain: String,
+	pub id: String,
+	#[serde(skip)]
+	pub normalized_id: String,
+	/// The ID of the link the alias leads to.
+	pub link_id: String,
+	pub created_at: i64,
+}
+
+/// An alias as it is requested through the admin API.
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({"id": "q3"}))]
+pub struct AliasRequest {
+	/// The ID the link should also be reachable with.
+	pub id: String,
+}
+
+impl Alias {
+	/// Stores a new alias leading to the link with the ID `link_id`.
+	/// The ID has to be checked to be free before.
+	pub(crate) async fn create(domain: &str, id: &str, normalized_id: &str, link_id: &str, conn: &mut SqliteConnection) -> Result<Self, ShortyError> {
+		let alias = Self {
+			domain: domain.to_owned(),
+			id: id.to_owned(),
+			normalized_id: normalized_id.to_owned(),
+			link_id: link_id.to_owned(),
+			created_at: time_now(),
+		};
+		sqlx::query!(
+			r#"
+			INSERT INTO aliases (domain, id, normalized_id, link_id, created_at)
+			VALUES ($1, $2, $3, $4, $5);
+			"#,
+			alias.domain,
+			alias.id,
+			alias.normalized_id,
+			alias.link_id,
+			alias.created_at,
+		)
+			.execute(&mut *conn)
+			.await?;
+
+
+		Ok(alias)
+	}
+
+	/// Retrieves the alias with this ID, or one whose ID is the same after normalization.
+	/// An alias with exactly this ID is preferred.
+	pub(crate) async fn find(domain: &str, id: &str, normalized_id: &str, conn: &mut SqliteConnection) -> Result<Option<Self>, ShortyError> {
+		let alias = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT * FROM aliases
+			WHERE domain = $1 AND (id = $2 OR normalized_id = $3)
+			ORDER BY id = $4 DESC
+			LIMIT 1;
+			"#,
+			domain,
+			id,
+			normalized_id,
+			id,
+		)
+			.fetch_optional(&mut *conn)
+			.await?;
+
+
+		Ok(alias)
+	}
+
+	/// Retrieves the aliases of the link, oldest first.
+	pub(crate) async fn for_link(domain: &str, link_id: &str, pool: &Pool<Sqlite>) -> Result<Vec<Self>, ShortyError> {
+		let aliases = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT * FROM aliases
+			WHERE domain = $1 AND link_id = $2
+			ORDER BY created_at, id;
+			"#,
+			domain,
+			link_id,
+		)
+			.fetch_all(pool)
+			.await?;
+
+
+		Ok(aliases)
+	}
+
+	/// Deletes the alias of the link. Returns whether there was one.
+	pub(crate) async fn delete(domain: &str, link_id: &str, id: &str, pool: &Pool<Sqlite>) -> Result<bool, ShortyError> {
+		let result = sqlx::query!(
+			r#"
+			DELETE FROM aliases
+			WHERE domain = $1 AND link_id = $2 AND id = $3;
+			"#,
+			domain,
+			link_id,
+			id,
+		)
+			.execute(pool)
+			.await?;
+
+
+		Ok(result.rows_affected() > 0)
+	}
+
+	/// Deletes the alias with this ID or this normalized ID, so the ID can be used for a link.
+	/// Returns whether there was one.
+	pub(crate) async fn release(domain: &str, id: &str, normalized_id: &str, conn: &mut SqliteConnection) -> Result<bool, ShortyError> {
+		let result = sqlx::query!(
+			r#"
+			DELETE FROM aliases
+			WHERE domain = $1 AND (id = $2 OR normalized_id = $3);
+			"#,
+			domain,
+			id,
+			normalized_id,
+		)
+			.execute(&mut *conn)
+			.await?;
+
+
+		Ok(result.rows_affected() > 0)
+	}
+
+	/// Deletes the aliases of the link, e.g. because its ID is used for a new link.
+	pub(crate) async fn delete_for(domain: &str, link_id: &str, conn: &mut SqliteConnection) -> Result<(), ShortyError> {
+		sqlx::query!(
+			r#"
+			DELETE FROM aliases
+			WHERE domain = $1 AND link_id = $2;
+			"#,
+			domain,
+			link_id,
+		)
+			.execute(&mut *conn)
+			.await?;
+
+
+		Ok(())
+	}
+}
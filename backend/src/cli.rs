@@ -0,0 +1,459 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::pin::pin;
+
+use chrono::{DateTime, Local, TimeZone};
+use clap::{Args, Parser, Subcommand};
+use futures_util::TryStreamExt;
+
+use crate::config::{Config, CONFIG, DEFAULT_CONFIG_LOCATION, SAMPLE_CONFIG};
+use crate::domain::Domain;
+use crate::importers::{self, Source};
+use crate::error::ShortyError;
+use crate::link::{Link, LinkConfig, LinkState, LinkStore};
+use crate::transfer::{ConflictPolicy, Format, ImportOutcome, ImportResult, parse};
+use crate::variant::VariantConfig;
+
+/// Small link shortener
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+	/// Location of the config file. Defaults to `./config.toml`, which may be absent.
+	#[arg(short, long, global = true, env = "SHORTY_CONFIG")]
+	pub config: Option<PathBuf>,
+	/// Override a config key, e.g. `--set port=8080`. Takes precedence over the config file and
+	/// environment variables. Can be given multiple times.
+	#[arg(short, long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+	pub overrides: Vec<(String, String)>,
+	#[command(subcommand)]
+	pub command: Option<Command>,
+}
+
+impl Cli {
+	/// Where the config file is or should be created.
+	#[must_use]
+	pub fn config_location(&self) -> PathBuf {
+		self.config.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_LOCATION))
+	}
+
+	/// The config file that should be loaded.
+	/// The default location is optional, but a location that was asked for explicitly has to exist.
+	///
+	/// # Errors
+	///
+	/// Errors if an explicitly given config file doesn't exist.
+	pub fn config_file(&self) -> Result<Option<PathBuf>, ShortyError> {
+		let location = self.config_location();
+		if location.exists() {
+			return Ok(Some(location));
+		}
+
+		if self.config.is_some() {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::NotFound,
+				format!("There is no config file at {}", location.display()),
+			).into());
+		}
+
+
+		Ok(None)
+	}
+}
+
+fn parse_key_value(argument: &str) -> Result<(String, String), String> {
+	let (key, value) = argument.split_once('=')
+		.ok_or_else(|| format!("expected KEY=VALUE, got `{argument}`"))?;
+
+
+	Ok((key.trim().to_owned(), value.to_owned()))
+}
+
+fn parse_time(argument: &str) -> Result<i64, String> {
+	DateTime::parse_from_rfc3339(argument)
+		.map(|time| time.timestamp_millis())
+		.map_err(|why| format!("expected a time like 2026-10-20T09:00:00+02:00, got `{argument}`: {why}"))
+}
+
+fn parse_variant(argument: &str) -> Result<VariantConfig, String> {
+	let (weight, redirect_to) = argument.split_once('=')
+		.ok_or_else(|| format!("expected WEIGHT=URL, got `{argument}`"))?;
+	let weight = weight.trim().parse()
+		.map_err(|_| format!("the weight must be a positive number, got `{weight}`"))?;
+
+
+	Ok(VariantConfig { redirect_to: redirect_to.to_owned(), weight })
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// Run the HTTP server. This is the default if no command is given.
+	Serve,
+	/// Manage the config file.
+	#[command(subcommand)]
+	Config(ConfigCommand),
+	/// Create the database if needed and apply all pending schema migrations.
+	Migrate,
+	/// Manage the links in the database.
+	#[command(subcommand)]
+	Links(LinksCommand),
+	/// Mark links that ran out of time or uses as expired.
+	Clean,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+	/// Write a sample config to the config location.
+	Init {
+		/// Overwrite the config file if it exists already.
+		#[arg(long)]
+		force: bool,
+	},
+	/// Check that the config file can be loaded.
+	Check,
+	/// Print the sample config, including all default values.
+	PrintDefaults,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LinksCommand {
+	/// Shorten a link.
+	Create(Box<CreateArgs>),
+	/// List the links in the database, oldest first, including deleted ones.
+	List {
+		/// How many links to list.
+		#[arg(long, default_value_t = 100)]
+		limit: i64,
+		/// How many links to skip.
+		#[arg(long, default_value_t = 0)]
+		offset: i64,
+	},
+	/// Show the details of a link.
+	Show {
+		id: String,
+		/// The host of the domain the link is on. Defaults to the default domain.
+		#[arg(long)]
+		domain: Option<String>,
+	},
+	/// Delete a link.
+	Delete {
+		id: String,
+		/// The host of the domain the link is on. Defaults to the default domain.
+		#[arg(long)]
+		domain: Option<String>,
+	},
+	/// Make a link reachable with a further ID, sharing its uses, expiry and stats.
+	Alias {
+		/// The ID of the link.
+		id: String,
+		/// The further ID.
+		alias: String,
+		/// The host of the domain the link is on. Defaults to the default domain.
+		#[arg(long)]
+		domain: Option<String>,
+	},
+	/// Export all links, whatever their state. Variants and aliases aren't included.
+	Export {
+		#[arg(long, value_enum, default_value_t)]
+		format: Format,
+		/// The file to write to. The links are written to stdout if this is missing.
+		#[arg(long)]
+		output: Option<PathBuf>,
+	},
+	/// Import links from an export of shorty or another link shortener, validating them like new links.
+	Import {
+		/// The file to read from.
+		file: PathBuf,
+		/// Defaults to the format of the file extension, or `jsonl` if it has none of the formats.
+		#[arg(long, value_enum, conflicts_with = "from")]
+		format: Option<Format>,
+		/// The link shortener the file was exported from, if it isn't an export of shorty.
+		#[arg(long, value_enum)]
+		from: Option<Source>,
+		/// The host of the domain links from other link shorteners are put on. Defaults to the default domain.
+		#[arg(long, requires = "from")]
+		domain: Option<String>,
+		/// What happens to links whose ID is taken.
+		#[arg(long, value_enum, default_value_t)]
+		on_conflict: ConflictPolicy,
+		/// Only report what the import would do, e.g. which IDs are taken, without importing anything.
+		#[arg(long)]
+		dry_run: bool,
+	},
+	/// Remove an alias from a link.
+	Unalias {
+		/// The ID of the link.
+		id: String,
+		/// The ID of the alias.
+		alias: String,
+		/// The host of the domain the link is on. Defaults to the default domain.
+		#[arg(long)]
+		domain: Option<String>,
+	},
+}
+
+#[derive(Debug, Args)]
+pub struct CreateArgs {
+	/// The link that should be shortened.
+	pub link: String,
+	/// Custom ID for the link.
+	#[arg(long)]
+	pub id: Option<String>,
+	/// How often the link may be used, 0 means unlimited. Defaults to the configured default.
+	#[arg(long)]
+	pub max_uses: Option<i64>,
+	/// How long the link is valid for in milliseconds, 0 means forever. Defaults to the configured default.
+	#[arg(long)]
+	pub valid_for: Option<i64>,
+	/// The status code the link redirects with, 301, 302, 307 or 308. Defaults to the configured default.
+	#[arg(long)]
+	pub redirect_type: Option<i64>,
+	/// Let the link match longer paths too, appending the rest of the path and the query to it.
+	#[arg(long)]
+	pub prefix: bool,
+	/// Treat the link as a template, filling `{1}` with the first path segment after the ID and `{name}` with the query parameter `name`.
+	#[arg(long, conflicts_with = "prefix")]
+	pub template: bool,
+	/// A query parameter added to the target on every redirect, unless it has it already. Can be given multiple times.
+	/// Defaults to the configured default.
+	#[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+	pub append_query: Vec<(String, String)>,
+	/// Rules sending some visitors somewhere else, as a JSON array like `[{"platforms": ["ios"], "redirect_to": "..."}]`.
+	#[arg(long)]
+	pub rules: Option<String>,
+	/// Another target to split the visitors between, with its weight. Can be given multiple times.
+	#[arg(long = "variant", value_name = "WEIGHT=URL", value_parser = parse_variant)]
+	pub variants: Vec<VariantConfig>,
+	/// The weight of the link itself if it has variants, 1 by default.
+	#[arg(long, requires = "variants")]
+	pub weight: Option<u32>,
+	/// Let visitors keep getting the same variant.
+	#[arg(long, requires = "variants")]
+	pub sticky: bool,
+	/// When the link goes live, like `2026-10-20T09:00:00+02:00`. It doesn't redirect before that.
+	#[arg(long, value_parser = parse_time)]
+	pub not_before: Option<i64>,
+	/// Where the link redirects to before it goes live, instead of showing that it isn't available yet.
+	#[arg(long, requires = "not_before")]
+	pub not_before_redirect_to: Option<String>,
+	/// Return an existing link to the same target without limits instead of creating a new one.
+	/// Defaults to the configured default.
+	#[arg(long, conflicts_with = "id")]
+	pub deduplicate: bool,
+	/// The host of the domain the link should be shortened on. Defaults to the default domain.
+	#[arg(long)]
+	pub domain: Option<String>,
+}
+
+/// Runs the `config` subcommands.
+/// None of them need the config to be loaded.
+///
+/// # Errors
+///
+/// Errors if the config file couldn't be written, read or parsed.
+pub fn run_config_command(command: &ConfigCommand, cli: &Cli) -> Result<(), ShortyError> {
+	let config_location = cli.config_location();
+	match command {
+		ConfigCommand::Init { force } => {
+			if config_location.exists() && !force {
+				return Err(ShortyError::ConfigExists(config_location.display().to_string()));
+			}
+
+			std::fs::write(&config_location, SAMPLE_CONFIG)?;
+			println!("Wrote a sample config to {}", config_location.display());
+		}
+		ConfigCommand::Check => {
+			let config_file = cli.config_file()?;
+			Config::load(config_file.as_deref(), &cli.overrides)?;
+			match config_file {
+				Some(path) => println!("The configuration from {} and the environment is valid.", path.display()),
+				None => println!("There is no config file, the configuration from the environment is valid."),
+			}
+		}
+		ConfigCommand::PrintDefaults => print!("{SAMPLE_CONFIG}"),
+	}
+
+
+	Ok(())
+}
+
+/// Runs the `links` subcommands against the configured database.
+///
+/// # Errors
+///
+/// Errors if the underlying [`LinkStore`] operation fails.
+pub async fn run_links_command(command: LinksCommand, link_store: &LinkStore) -> Result<(), ShortyError> {
+	match command {
+		LinksCommand::Create(args) => {
+			let args = *args;
+			let link_config = LinkConfig {
+				custom_id: args.id,
+				max_uses: args.max_uses,
+				valid_for: args.valid_for,
+				redirect_type: args.redirect_type,
+				prefix: Some(args.prefix),
+				template: Some(args.template),
+				append_query: (!args.append_query.is_empty()).then(|| args.append_query.into_iter().collect()),
+				rules: args.rules
+					.map(|rules| serde_json::from_str(rules.as_str()))
+					.transpose()
+					.map_err(|why| ShortyError::InvalidRule(why.to_string()))?,
+				variants: Some(args.variants),
+				weight: args.weight,
+				sticky: Some(args.sticky),
+				not_before: args.not_before,
+				not_before_redirect_to: args.not_before_redirect_to,
+				deduplicate: args.deduplicate.then_some(true),
+				domain: args.domain,
+				..LinkConfig::new(args.link)
+			};
+			let default_domain = Domain::default_domain(&CONFIG.load());
+			let link = link_store.create_link_with_config(link_config, &default_domain).await?;
+			println!("{}", link.formatted());
+		}
+		LinksCommand::List { limit, offset } => {
+			for link in link_store.list(limit, offset).await? {
+				println!("{}\t{}\t{}", link.formatted(), link.state().as_str(), link.redirect_to);
+			}
+		}
+		LinksCommand::Show { id, domain } => {
+			let domain = Domain::requested_or_default(domain.as_deref())?;
+			let Some(link) = link_store.find(&domain, id.as_str()).await? else {
+				return Err(ShortyError::LinkNotFound(id));
+			};
+			print_link(&link);
+			for alias in link_store.aliases(&domain, link.id.as_str()).await? {
+				println!("Alias:       {}", alias.id);
+			}
+			for variant in link_store.variants(&domain, link.id.as_str()).await? {
+				println!("Variant {}:   {} (weight {}, used {} times)", variant.position, variant.redirect_to, variant.weight, variant.invocations);
+			}
+			for previous in link_store.history(&domain, link.id.as_str()).await? {
+				println!("Previously:  {} (created at {})", previous.redirect_to, format_timestamp(previous.created_at));
+			}
+		}
+		LinksCommand::Delete { id, domain } => {
+			let domain = Domain::requested_or_default(domain.as_deref())?;
+			if !link_store.delete(&domain, id.as_str()).await? {
+				return Err(ShortyError::LinkNotFound(id));
+			}
+			println!("Deleted {id}.");
+		}
+		LinksCommand::Alias { id, alias, domain } => {
+			let domain = Domain::requested_or_default(domain.as_deref())?;
+			let Some(alias) = link_store.add_alias(&domain, id.as_str(), alias.as_str()).await? else {
+				return Err(ShortyError::LinkNotFound(id));
+			};
+			println!("{} now leads to {}.", alias.id, alias.link_id);
+		}
+		LinksCommand::Export { format, output } => {
+			let mut writer: Box<dyn Write> = match output {
+				Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+				None => Box::new(BufWriter::new(std::io::stdout().lock())),
+			};
+			let mut chunks = pin!(link_store.export(format));
+			while let Some(chunk) = chunks.try_next().await? {
+				writer.write_all(&chunk)?;
+			}
+			writer.flush()?;
+		}
+		LinksCommand::Import { file, format, from, domain, on_conflict, dry_run } => {
+			let input = std::fs::read(&file)?;
+			let links = if let Some(source) = from {
+				let domain = Domain::requested_or_default(domain.as_deref())?;
+				importers::parse(source, input.as_slice(), domain.name.as_str())
+			} else {
+				let format = format
+					.or_else(|| file.extension().and_then(|extension| Format::from_extension(extension.to_str()?)))
+					.unwrap_or_default();
+				parse(format, input.as_slice())
+			};
+			let results = link_store.import(links, on_conflict, dry_run).await?;
+			print_import_results(results.as_slice());
+			if dry_run {
+				println!("This was a dry run, nothing was imported.");
+			}
+		}
+		LinksCommand::Unalias { id, alias, domain } => {
+			let domain = Domain::requested_or_default(domain.as_deref())?;
+			if !link_store.remove_alias(&domain, id.as_str(), alias.as_str()).await? {
+				return Err(ShortyError::AliasNotFound(id, alias));
+			}
+			println!("Removed the alias {alias} from {id}.");
+		}
+	}
+
+
+	Ok(())
+}
+
+fn print_link(link: &Link) {
+	let max_uses = if link.max_uses == 0 { "unlimited".to_owned() } else { link.max_uses.to_string() };
+	let expires_at = link.expires_at().map_or_else(|| "never".to_owned(), format_timestamp);
+
+	println!("ID:          {}", link.id);
+	if !link.domain.is_empty() {
+		println!("Domain:      {}", link.domain);
+	}
+	println!("URL:         {}", link.formatted());
+	println!("Redirect to: {}", link.redirect_to);
+	println!("Uses:        {} of {max_uses}", link.invocations);
+	let kind = if link.template {
+		", filling in the template"
+	} else if link.prefix {
+		", passing on paths and queries"
+	} else {
+		""
+	};
+	println!("Redirect:    {}{kind}", link.redirect_type);
+	if let Some(ref append_query) = link.append_query {
+		println!("Appends:     {}", if append_query.is_empty() { "nothing" } else { append_query });
+	}
+	for (index, rule) in link.rules().iter().enumerate() {
+		println!("Rule {}:      {}", index + 1, serde_json::to_string(rule).unwrap_or_default());
+	}
+	println!("Created at:  {}", format_timestamp(link.created_at));
+	if let Some(not_before) = link.not_before {
+		let fallback = link.not_before_redirect_to.as_ref().map_or_else(String::new, |target| format!(", redirects to {target} before"));
+		println!("Starts at:   {}{fallback}", format_timestamp(not_before));
+	}
+	println!("Expires at:  {expires_at}");
+	match link.state() {
+		LinkState::Disabled => println!("Status:      disabled, {}", link.disabled_reason.as_deref().unwrap_or("no reason")),
+		state => println!("Status:      {}", state.as_str()),
+	}
+	if let Some(status_changed_at) = link.status_changed_at {
+		println!("Since:       {}", format_timestamp(status_changed_at));
+	}
+}
+
+/// Prints what happened to every link that wasn't simply created, and how many links had which outcome.
+fn print_import_results(results: &[ImportResult]) {
+	for result in results {
+		match result.outcome {
+			ImportOutcome::Created | ImportOutcome::Overwritten => {}
+			ImportOutcome::Renamed => println!("Line {}: renamed {} to {}", result.line, result.id, result.new_id.as_deref().unwrap_or_default()),
+			ImportOutcome::Skipped | ImportOutcome::Failed => {
+				let outcome = if result.outcome == ImportOutcome::Skipped { "skipped" } else { "failed" };
+				println!("Line {}: {outcome} {}: {}", result.line, result.id, result.error.as_deref().unwrap_or_default());
+			}
+		}
+	}
+
+	let count = |outcome| results.iter().filter(|result| result.outcome == outcome).count();
+	println!(
+		"{} created, {} overwritten, {} renamed, {} skipped, {} failed.",
+		count(ImportOutcome::Created),
+		count(ImportOutcome::Overwritten),
+		count(ImportOutcome::Renamed),
+		count(ImportOutcome::Skipped),
+		count(ImportOutcome::Failed),
+	);
+}
+
+/// Formats a timestamp in milliseconds as local time.
+fn format_timestamp(millis: i64) -> String {
+	Local.timestamp_millis_opt(millis)
+		.single()
+		.map_or_else(|| millis.to_string(), |time| time.to_rfc3339())
+}
@@ -1,10 +1,165 @@
+use std::collections::BTreeMap;
 use std::env::VarError;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use arc_swap::{ArcSwap, Guard};
 use serde::{Serialize, Deserialize};
-use tracing::error;
+use tracing::{error, info, warn};
 use utoipa::ToSchema;
 
+use crate::config_validation::unknown_keys;
+use crate::domain_filter::DomainFilter;
+use crate::error::ShortyError;
+use crate::link::LinkStore;
+
 pub const SAMPLE_CONFIG: &str = include_str!(concat!(env!("OUT_DIR"), "/config.toml.sample"));
 
+/// Where the config file is looked for if no location is given.
+pub const DEFAULT_CONFIG_LOCATION: &str = "./config.toml";
+
+/// Prefix of the environment variables that override config keys.
+const ENV_PREFIX: &str = "SHORTY_";
+
+/// Environment variables with the [`ENV_PREFIX`] that aren't config keys.
+/// `SHORTY_WEBSITE` is the old name of `SHORTY_FRONTEND_LOCATION` and handled separately.
+const NON_CONFIG_VARIABLES: [&str; 2] = ["CONFIG", "WEBSITE"];
+
+/// How often the config file and the domain list files are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+static LOADED_CONFIG: OnceLock<LoadedConfig> = OnceLock::new();
+
+/// The config the application is running with, see [`GlobalConfig::load`].
+pub static CONFIG: GlobalConfig = GlobalConfig;
+
+pub struct GlobalConfig;
+
+impl GlobalConfig {
+	/// Returns the current config.
+	/// The config might get replaced while the returned value is held, so it shouldn't be held
+	/// longer than necessary, e.g. not across requests.
+	///
+	/// # Panics
+	///
+	/// Panics if the config hasn't been [initialized](init) yet.
+	pub fn load(&self) -> Guard<Arc<Config>> {
+		LOADED_CONFIG.get()
+			.expect("The config was accessed before it was loaded.")
+			.current
+			.load()
+	}
+}
+
+/// The config together with where it was loaded from, so it can be reloaded.
+struct LoadedConfig {
+	current: ArcSwap<Config>,
+	file: Option<PathBuf>,
+	overrides: Vec<(String, String)>,
+}
+
+/// Sets the config the application runs with and remembers its sources for [`reload`].
+/// Only the first call has an effect.
+pub fn init(config: Config, file: Option<PathBuf>, overrides: Vec<(String, String)>) {
+	let loaded = LoadedConfig {
+		current: ArcSwap::from_pointee(config),
+		file,
+		overrides,
+	};
+
+	if LOADED_CONFIG.set(loaded).is_err() {
+		error!("The config was already initialized, ignoring the new one.");
+	}
+}
+
+/// Loads the config again from the same sources it was initially loaded from and replaces the current one.
+/// Keys that are only used on startup keep their current value, changes to them are only logged.
+/// If the `*_ids` options changed, the IDs in the database are normalized again first.
+///
+/// # Errors
+///
+/// Errors if the new config couldn't be loaded or the IDs can't be normalized with it,
+/// the current one stays in use in that case.
+///
+/// # Panics
+///
+/// Panics if the config hasn't been [initialized](init) yet.
+pub async fn reload(link_store: &LinkStore) -> Result<(), ShortyError> {
+	let loaded = LOADED_CONFIG.get().expect("The config was reloaded before it was loaded.");
+	let mut config = Config::load(loaded.file.as_deref(), &loaded.overrides)?;
+	let current = loaded.current.load_full();
+
+	for key in config.keep_startup_values(&current) {
+		warn!("`{key}` was changed, this only takes effect after a restart.");
+	}
+
+	if config.id_normalization() != current.id_normalization() {
+		let changed = link_store.normalize_ids(&config).await?;
+		info!("The ID normalization changed, normalized {changed} IDs again.");
+	}
+
+	loaded.current.store(Arc::new(config));
+	info!("Reloaded the config.");
+
+
+	Ok(())
+}
+
+/// Reloads the config on SIGHUP and whenever the config file or one of the domain list files changes.
+/// Runs until the task is dropped.
+pub async fn watch_for_changes(link_store: LinkStore) {
+	let mut last_modified = watched_files_modified_at();
+
+	#[cfg(unix)]
+	let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+		.expect("Error installing the SIGHUP handler.");
+
+	loop {
+		#[cfg(unix)]
+		let signal = hangup.recv();
+		#[cfg(not(unix))]
+		let signal = std::future::pending::<Option<()>>();
+
+		tokio::select! {
+			_ = signal => info!("Received SIGHUP, reloading the config."),
+			() = tokio::time::sleep(WATCH_INTERVAL) => {
+				let modified = watched_files_modified_at();
+				if modified == last_modified {
+					continue;
+				}
+				info!("The config file or a domain list changed, reloading the config.");
+			},
+		}
+
+		if let Err(why) = reload(&link_store).await {
+			error!("Failed to reload the config, keeping the current one.\n{why}");
+		}
+		last_modified = watched_files_modified_at();
+	}
+}
+
+/// When the config file and the domain list files of the current config were last modified.
+fn watched_files_modified_at() -> Vec<Option<SystemTime>> {
+	let Some(loaded) = LOADED_CONFIG.get() else {
+		return Vec::new();
+	};
+	let config = loaded.current.load();
+
+	let lists = [&config.allowed_domains_file, &config.blocked_domains_file]
+		.into_iter()
+		.flatten()
+		.map(PathBuf::from);
+
+
+	loaded.file.clone().into_iter()
+		.chain(lists)
+		.map(|file| std::fs::metadata(file).ok().and_then(|metadata| metadata.modified().ok()))
+		.collect()
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Config {
 	#[serde(default = "listen_url_default")]
@@ -12,12 +167,14 @@ pub struct Config {
 	pub listen_url: String,
 	/// The public URL that gets used for shortened links.
 	// It is different from the listen_url if shorty is run behind a reverse proxy.
+	#[serde(default = "public_url_default")]
 	pub public_url: String,
 	/// The listen port.
 	#[serde(default = "port_default")]
 	#[serde(skip_serializing)]
 	pub port: u16,
 	/// The database connection String.
+	#[serde(default = "database_location_default")]
 	#[serde(skip_serializing)]
 	pub database_location: String,
 	/// The maximum length a link should be allowed to have.
@@ -29,42 +186,266 @@ pub struct Config {
 	/// Maximum allowed length of a custom ID.
 	#[serde(default = "max_custom_id_length_default")]
 	pub max_custom_id_length: usize,
+	/// The maximum number of links that can be created in one batch.
+	#[serde(default = "max_batch_size_default")]
+	pub max_batch_size: usize,
+	/// The maximum number of entries the admin API lists at once.
+	#[serde(default = "max_page_size_default")]
+	#[serde(skip_serializing)]
+	pub max_page_size: u32,
 	/// Default max uses for a link.
 	#[serde(default = "max_uses_default")]
 	pub default_max_uses: i64,
 	/// Default duration a link is valid for.
 	#[serde(default = "valid_for_duration_default")]
 	pub default_valid_for: i64,
+	/// The status code links redirect with if they don't have their own, 301, 302, 307 or 308.
+	#[serde(default = "redirect_type_default")]
+	pub default_redirect_type: i64,
+	/// Query parameters added to the targets of links that don't have their own, like `utm_source`.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub default_append_query: BTreeMap<String, String>,
+	/// Whether shortening a target again returns an existing link without limits instead of creating a new one.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub deduplicate_links: bool,
+	/// How long the ID of an expired link can't be used for a new link, in milliseconds.
+	#[serde(default = "expired_id_grace_period_default")]
+	#[serde(skip_serializing)]
+	pub expired_id_grace_period: i64,
 	/// Location for custom frontend.
 	#[serde(default)]
 	#[serde(skip_serializing)]
 	pub frontend_location: Option<String>,
+	/// Seconds to wait for in-flight requests when shutting down.
+	#[serde(default = "shutdown_timeout_default")]
+	#[serde(skip_serializing)]
+	pub shutdown_timeout: u64,
+	/// The schemes links may redirect to.
+	#[serde(default = "allowed_target_schemes_default")]
+	pub allowed_target_schemes: Vec<String>,
+	/// The scheme used for links that are shortened without one.
+	#[serde(default = "default_target_scheme_default")]
+	pub default_target_scheme: String,
+	/// The only domains links may redirect to. Every domain is allowed if this is empty.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub allowed_domains: Vec<String>,
+	/// A file with additional allowed domains, one per line.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub allowed_domains_file: Option<String>,
+	/// Domains links must not redirect to.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub blocked_domains: Vec<String>,
+	/// A file with additional blocked domains, one per line.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub blocked_domains_file: Option<String>,
+	/// The filter built from the domain lists and files above.
+	#[serde(skip)]
+	pub domain_filter: DomainFilter,
+	/// After how many reports from distinct reporters a link gets disabled. 0 never disables links automatically.
+	#[serde(default = "reports_to_disable_default")]
+	#[serde(skip_serializing)]
+	pub reports_to_disable: u32,
+	/// The addresses of reverse proxies whose `X-Forwarded-For` header is trusted to tell who sent a request.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub trusted_proxies: Vec<String>,
+	/// A token with the [`Role::Admin`] role.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub admin_token: Option<String>,
+	/// Tokens for the admin API, each with its own role.
+	/// The admin API is unavailable if there are none and there is no `admin_token`.
+	#[serde(default)]
+	#[serde(skip_serializing)]
+	pub api_tokens: Vec<ApiToken>,
+	/// Whether custom IDs differing only in upper and lower case are the same ID.
+	#[serde(default)]
+	pub case_insensitive_ids: bool,
+	/// Whether custom IDs are compared after Unicode NFKC normalization, so e.g. `ﬁ` and `fi` are the same ID.
+	#[serde(default)]
+	pub normalize_unicode_ids: bool,
+	/// Whether custom IDs that look alike, like `d0cs` and `dOcs`, are the same ID.
+	#[serde(default)]
+	pub fold_confusable_ids: bool,
+	/// Additional domains, each with their own namespace of IDs.
+	#[serde(default)]
+	pub domains: Vec<DomainConfig>,
+}
+
+/// Configuration of an additional domain links can be shortened on.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct DomainConfig {
+	/// The public URL that gets used for links shortened on this domain.
+	pub public_url: String,
+	/// The host requests for this domain are sent to. Defaults to the host of the `public_url`.
+	#[serde(default)]
+	pub host: Option<String>,
+	/// Default max uses for links on this domain, instead of the global default.
+	#[serde(default)]
+	pub default_max_uses: Option<i64>,
+	/// Default duration links on this domain are valid for, instead of the global default.
+	#[serde(default)]
+	pub default_valid_for: Option<i64>,
+	/// Query parameters added to the targets of links on this domain, instead of the global default.
+	#[serde(default)]
+	pub default_append_query: Option<BTreeMap<String, String>>,
+}
+
+/// A token for the admin API.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiToken {
+	/// Identifies who uses the token in the logs.
+	pub name: String,
+	/// The secret sent as `Authorization: Bearer <token>`.
+	pub token: String,
+	/// What the token may be used for.
+	pub role: Role,
+}
+
+/// What a token for the admin API may do. Every role may do everything the roles before it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+	/// Can list links and reports and view the totals.
+	Viewer,
+	/// Can also disable, reinstate and ban links.
+	Moderator,
+	/// Can also delete links.
+	Admin,
+}
+
+impl Display for Role {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Role::Viewer => write!(f, "viewer"),
+			Role::Moderator => write!(f, "moderator"),
+			Role::Admin => write!(f, "admin"),
+		}
+	}
+}
+
+impl DomainConfig {
+	/// The lowercase host requests for this domain are sent to.
+	#[must_use]
+	pub fn host(&self) -> String {
+		self.host.clone()
+			.or_else(|| url::Url::parse(self.public_url.as_str()).ok()?.host_str().map(str::to_owned))
+			.unwrap_or_default()
+			.to_lowercase()
+	}
 }
 
 impl Config {
+	/// Builds the config from its sources. Later sources take precedence over earlier ones:
+	/// 1. The compiled in defaults.
+	/// 2. The config file, if there is one.
+	/// 3. `SHORTY_<KEY>` environment variables, e.g. `SHORTY_PUBLIC_URL` for `public_url`.
+	/// 4. The `overrides`, usually passed on the command line.
+	///
 	/// # Errors
-	/// Errors when the config couldn't be deserialized.
-	pub fn new(config: &str) -> Result<Self, toml::de::Error> {
-		let mut config: Config = toml::from_str(config)?;
+	///
+	/// Errors when the config file couldn't be read, the merged config couldn't be deserialized
+	/// or it failed [validation](Config::validate).
+	pub fn load(file: Option<&Path>, overrides: &[(String, String)]) -> Result<Self, ShortyError> {
+		let mut table = match file {
+			Some(path) => toml::from_str(std::fs::read_to_string(path)?.as_str())?,
+			None => toml::Table::new(),
+		};
+
+		// Variables that aren't valid UTF-8 might belong to anything, they must not keep the config from loading.
+		for (variable, value) in std::env::vars_os() {
+			let Some(key) = variable.to_str().and_then(|variable| variable.strip_prefix(ENV_PREFIX)) else {
+				continue;
+			};
+			if NON_CONFIG_VARIABLES.contains(&key) {
+				continue;
+			}
+			let Some(value) = value.to_str() else {
+				warn!("Ignoring {ENV_PREFIX}{key}, its value isn't valid UTF-8.");
+				continue;
+			};
+
+			let key = key.to_lowercase();
+			let value = parse_value(key.as_str(), value);
+			table.insert(key, value);
+		}
+
+		for (key, value) in overrides {
+			table.insert(key.clone(), parse_value(key.as_str(), value.as_str()));
+		}
+
+		for (key, closest) in unknown_keys(&table) {
+			match closest {
+				Some(closest) => warn!("Ignoring unknown config key `{key}`, did you mean `{closest}`?"),
+				None => warn!("Ignoring unknown config key `{key}`."),
+			}
+		}
+
+		let mut config: Config = table.try_into()?;
 
 		if config.frontend_location.is_none() {
 			match std::env::var("SHORTY_WEBSITE") {
 				Ok(path) => { config.frontend_location = Some(path) },
-				#[allow(unused)]
-				Err(e) => {
-					#[cfg(not(feature = "integrated-frontend"))]
-					panic!("Shorty was compiled without the `integrated-frontend` feature, therefore the frontend_location key is mandatory");
-
-					if e != VarError::NotPresent {
-						error!("{e}");
-					}
-				},
+				Err(VarError::NotPresent) => {},
+				Err(e) => error!("{e}"),
 			}
 		}
 
+		config.validate()?;
+		config.domain_filter = DomainFilter::load(&config)?;
+
+
 		Ok(config)
 	}
 
+	/// Whether requests from this address are sent by one of the `trusted_proxies`.
+	#[must_use]
+	pub fn is_trusted_proxy(&self, address: IpAddr) -> bool {
+		self.trusted_proxies.iter().any(|proxy| proxy.parse::<IpAddr>().is_ok_and(|proxy| proxy == address))
+	}
+
+	/// The options IDs are normalized with, see [`normalize_id`](crate::util::normalize_id).
+	#[must_use]
+	pub fn id_normalization(&self) -> (bool, bool, bool) {
+		(self.case_insensitive_ids, self.normalize_unicode_ids, self.fold_confusable_ids)
+	}
+
+	/// Sets the keys that are only used on startup to the values from `running`, since changing
+	/// them requires a restart. Returns the keys that had a different value.
+	fn keep_startup_values(&mut self, running: &Config) -> Vec<&'static str> {
+		let mut changed = Vec::new();
+
+		if self.listen_url != running.listen_url {
+			changed.push("listen_url");
+			self.listen_url.clone_from(&running.listen_url);
+		}
+		if self.port != running.port {
+			changed.push("port");
+			self.port = running.port;
+		}
+		if self.database_location != running.database_location {
+			changed.push("database_location");
+			self.database_location.clone_from(&running.database_location);
+		}
+		if self.max_json_size != running.max_json_size {
+			changed.push("max_json_size");
+			self.max_json_size = running.max_json_size;
+		}
+		if self.shutdown_timeout != running.shutdown_timeout {
+			changed.push("shutdown_timeout");
+			self.shutdown_timeout = running.shutdown_timeout;
+		}
+
+
+		changed
+	}
+
 	#[allow(clippy::missing_panics_doc)]
 	#[must_use]
 	pub fn json_string(&self) -> String {
@@ -72,8 +453,39 @@ impl Config {
 	}
 }
 
+/// Parses a value for the key from the environment or command line as TOML, so `8080` becomes an integer
+/// and `[1, 2]` an array. Anything that isn't valid TOML is taken as a plain string, and so is a value
+/// the key only takes as a string, like a token that happens to be all digits.
+fn parse_value(key: &str, value: &str) -> toml::Value {
+	let string = toml::Value::String(value.to_owned());
+	let Some(parsed) = toml::from_str::<toml::Table>(format!("value = {value}").as_str())
+		.ok()
+		.and_then(|mut table| table.remove("value")) else {
+		return string;
+	};
+
+	// Every other key has a default, so the config only fails to deserialize if the key doesn't take the value.
+	let fits = |value: &toml::Value| toml::Table::from_iter([(key.to_owned(), value.clone())]).try_into::<Config>().is_ok();
+	if !fits(&parsed) && fits(&string) {
+		return string;
+	}
+
+
+	parsed
+}
+
 fn listen_url_default() -> String { env!("LISTEN_URL_DEFAULT").to_owned() }
 
+fn public_url_default() -> String { env!("PUBLIC_URL_DEFAULT").to_owned() }
+
+fn database_location_default() -> String { env!("DATABASE_LOCATION_DEFAULT").to_owned() }
+
+fn allowed_target_schemes_default() -> Vec<String> {
+	env!("ALLOWED_TARGET_SCHEMES_DEFAULT").split(',').map(str::to_owned).collect()
+}
+
+fn default_target_scheme_default() -> String { env!("DEFAULT_TARGET_SCHEME_DEFAULT").to_owned() }
+
 const fn port_default() -> u16 {
 	konst::unwrap_ctx!(konst::primitive::parse_u16(env!("PORT_DEFAULT")))
 }
@@ -89,6 +501,22 @@ const fn max_custom_id_length_default() -> usize {
 	konst::unwrap_ctx!(konst::primitive::parse_usize(env!("MAX_CUSTOM_ID_LENGTH_DEFAULT")))
 }
 
+const fn max_batch_size_default() -> usize {
+	konst::unwrap_ctx!(konst::primitive::parse_usize(env!("MAX_BATCH_SIZE_DEFAULT")))
+}
+
+const fn max_page_size_default() -> u32 {
+	konst::unwrap_ctx!(konst::primitive::parse_u32(env!("MAX_PAGE_SIZE_DEFAULT")))
+}
+
+const fn shutdown_timeout_default() -> u64 {
+	konst::unwrap_ctx!(konst::primitive::parse_u64(env!("SHUTDOWN_TIMEOUT_DEFAULT")))
+}
+
+const fn reports_to_disable_default() -> u32 {
+	konst::unwrap_ctx!(konst::primitive::parse_u32(env!("REPORTS_TO_DISABLE_DEFAULT")))
+}
+
 // Link configuration default values
 
 const fn max_uses_default() -> i64 {
@@ -98,3 +526,11 @@ const fn max_uses_default() -> i64 {
 const fn valid_for_duration_default() -> i64 {
 	konst::unwrap_ctx!(konst::primitive::parse_i64(env!("VALID_FOR_DURATION_DEFAULT")))
 }
+
+const fn redirect_type_default() -> i64 {
+	konst::unwrap_ctx!(konst::primitive::parse_i64(env!("REDIRECT_TYPE_DEFAULT")))
+}
+
+const fn expired_id_grace_period_default() -> i64 {
+	konst::unwrap_ctx!(konst::primitive::parse_i64(env!("EXPIRED_ID_GRACE_PERIOD_DEFAULT")))
+}
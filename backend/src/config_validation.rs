@@ -0,0 +1,383 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::de::Visitor;
+use url::Url;
+
+use crate::config::Config;
+use crate::domain_filter::{check_list_file, check_patterns};
+use crate::redirect::RedirectType;
+
+/// Something that is wrong with a config key.
+#[derive(Debug)]
+pub struct ConfigProblem {
+	pub key: &'static str,
+	pub message: String,
+	pub suggestion: Option<String>,
+}
+
+impl Display for ConfigProblem {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "`{}`: {}", self.key, self.message)?;
+		if let Some(ref suggestion) = self.suggestion {
+			write!(f, " {suggestion}")?;
+		}
+
+
+		Ok(())
+	}
+}
+
+/// All problems found while validating a config.
+#[derive(Debug)]
+pub struct ConfigProblems(pub Vec<ConfigProblem>);
+
+impl std::error::Error for ConfigProblems {}
+
+impl Display for ConfigProblems {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let plural = if self.0.len() == 1 { "" } else { "s" };
+		write!(f, "The config has {} problem{plural}:", self.0.len())?;
+		for problem in &self.0 {
+			write!(f, "\n  - {problem}")?;
+		}
+
+
+		Ok(())
+	}
+}
+
+impl Config {
+	/// Checks the config for values that would only fail once the server is running, or not at all
+	/// and just lead to weird behaviour.
+	///
+	/// # Errors
+	///
+	/// Returns every problem that was found.
+	pub fn validate(&self) -> Result<(), ConfigProblems> {
+		let mut problems = Vec::new();
+		let mut problem = |key, message: String, suggestion: Option<String>| {
+			problems.push(ConfigProblem { key, message, suggestion });
+		};
+
+		if self.listen_url.is_empty() {
+			problem("listen_url", "must not be empty.".to_owned(), Some("Use '127.0.0.1' to only accept local connections or '0.0.0.0' for all.".to_owned()));
+		} else if (self.listen_url.as_str(), self.port).to_socket_addrs().is_err() {
+			problem("listen_url", format!("'{}' is not an address shorty can listen on.", self.listen_url), Some("Use an IP address like '127.0.0.1' or '0.0.0.0'.".to_owned()));
+		}
+
+		if self.port == 0 {
+			problem("port", "must not be 0.".to_owned(), Some(format!("The default is {}.", env!("PORT_DEFAULT"))));
+		}
+
+		if let Some((message, suggestion)) = check_public_url(self.public_url.as_str()) {
+			problem("public_url", message, suggestion);
+		}
+
+		if let Some((message, suggestion)) = check_database_location(self.database_location.as_str()) {
+			problem("database_location", message, suggestion);
+		}
+
+		if self.max_link_length == 0 {
+			problem("max_link_length", "must be at least 1, otherwise no link can be shortened.".to_owned(), Some(format!("The default is {}.", env!("MAX_LINK_LENGTH_DEFAULT"))));
+		}
+
+		if self.max_json_size == 0 {
+			problem("max_json_size", "must be at least 1, otherwise no custom link can be created.".to_owned(), Some(format!("The default is {} bytes.", env!("MAX_JSON_SIZE_DEFAULT"))));
+		}
+
+		if self.max_batch_size == 0 {
+			problem("max_batch_size", "must be at least 1, otherwise no links can be created in a batch.".to_owned(), Some(format!("The default is {}.", env!("MAX_BATCH_SIZE_DEFAULT"))));
+		}
+
+		if self.max_page_size == 0 {
+			problem("max_page_size", "must be at least 1, otherwise the admin API can't list anything.".to_owned(), Some(format!("The default is {}.", env!("MAX_PAGE_SIZE_DEFAULT"))));
+		}
+
+		if self.max_custom_id_length == 0 {
+			problem("max_custom_id_length", "must be at least 1, otherwise no custom ID can be used.".to_owned(), Some(format!("The default is {}.", env!("MAX_CUSTOM_ID_LENGTH_DEFAULT"))));
+		}
+
+		if self.default_max_uses < 0 {
+			problem("default_max_uses", format!("is {}, but must not be negative.", self.default_max_uses), Some("Use 0 for unlimited uses.".to_owned()));
+		}
+
+		if self.default_valid_for < 0 {
+			problem("default_valid_for", format!("is {}, but must not be negative.", self.default_valid_for), Some("It is in milliseconds, use 0 for links that never expire.".to_owned()));
+		}
+
+		if RedirectType::try_from(self.default_redirect_type).is_err() {
+			problem("default_redirect_type", format!("is {}, but must be 301, 302, 307 or 308.", self.default_redirect_type), Some("Use 307 or 302 for links that may change, 308 or 301 for links that never do.".to_owned()));
+		}
+
+		if self.expired_id_grace_period < 0 {
+			problem("expired_id_grace_period", format!("is {}, but must not be negative.", self.expired_id_grace_period), Some("It is in milliseconds, use 0 to allow reusing IDs right after they expired.".to_owned()));
+		}
+
+		if self.allowed_target_schemes.is_empty() {
+			problem("allowed_target_schemes", "must not be empty, otherwise no link can be shortened.".to_owned(), Some("The default is ['http', 'https'].".to_owned()));
+		}
+		for scheme in &self.allowed_target_schemes {
+			if !is_valid_scheme(scheme) {
+				problem("allowed_target_schemes", format!("'{scheme}' is not a valid scheme."), Some("Schemes are written without '://', e.g. 'https'.".to_owned()));
+			}
+		}
+
+		if !is_valid_scheme(self.default_target_scheme.as_str()) {
+			problem("default_target_scheme", format!("'{}' is not a valid scheme.", self.default_target_scheme), Some("Schemes are written without '://', e.g. 'https'.".to_owned()));
+		} else if !self.allowed_target_schemes.iter().any(|scheme| scheme.eq_ignore_ascii_case(self.default_target_scheme.as_str())) {
+			problem("default_target_scheme", format!("'{}' is not in `allowed_target_schemes`.", self.default_target_scheme), Some("Add it there or choose an allowed scheme.".to_owned()));
+		}
+
+		for message in check_patterns(self.allowed_domains.iter().map(String::as_str)) {
+			problem("allowed_domains", message, Some("Use 'example.com' for the domain itself or '*.example.com' for its subdomains.".to_owned()));
+		}
+		for message in check_patterns(self.blocked_domains.iter().map(String::as_str)) {
+			problem("blocked_domains", message, Some("Use 'example.com' for the domain itself or '*.example.com' for its subdomains.".to_owned()));
+		}
+		if let Some(ref file) = self.allowed_domains_file {
+			for message in check_list_file(file) {
+				problem("allowed_domains_file", message, None);
+			}
+		}
+		if let Some(ref file) = self.blocked_domains_file {
+			for message in check_list_file(file) {
+				problem("blocked_domains_file", message, None);
+			}
+		}
+
+		for proxy in &self.trusted_proxies {
+			if proxy.parse::<IpAddr>().is_err() {
+				problem("trusted_proxies", format!("'{proxy}' is not an IP address."), Some("Use the address the proxy connects from, e.g. '127.0.0.1'.".to_owned()));
+			}
+		}
+
+		if let Some(ref admin_token) = self.admin_token {
+			if admin_token.len() < MIN_ADMIN_TOKEN_LENGTH {
+				problem("admin_token", format!("must be at least {MIN_ADMIN_TOKEN_LENGTH} characters long."), Some("Generate one with e.g. `openssl rand -hex 32`.".to_owned()));
+			}
+		}
+
+		let mut tokens: Vec<&str> = self.admin_token.iter().map(String::as_str).collect();
+		for (index, api_token) in self.api_tokens.iter().enumerate() {
+			if api_token.name.trim().is_empty() {
+				problem("api_tokens", format!("token {} has an empty `name`.", index + 1), Some("Name it after whoever uses it.".to_owned()));
+			}
+			if api_token.token.len() < MIN_ADMIN_TOKEN_LENGTH {
+				problem("api_tokens", format!("the token of '{}' must be at least {MIN_ADMIN_TOKEN_LENGTH} characters long.", api_token.name), Some("Generate one with e.g. `openssl rand -hex 32`.".to_owned()));
+			} else if tokens.contains(&api_token.token.as_str()) {
+				problem("api_tokens", format!("the token of '{}' is used more than once.", api_token.name), Some("Every token has to be unique.".to_owned()));
+			}
+			tokens.push(api_token.token.as_str());
+		}
+
+		let mut hosts = Vec::new();
+		for (index, domain) in self.domains.iter().enumerate() {
+			let host = domain.host();
+			if let Some((message, suggestion)) = check_public_url(domain.public_url.as_str()) {
+				problem("domains", format!("the `public_url` of domain {}: {message}", index + 1), suggestion);
+			} else if host.is_empty() {
+				problem("domains", format!("domain {} has an empty `host`.", index + 1), Some("Remove the key to use the host of its `public_url`.".to_owned()));
+			} else if hosts.contains(&host) {
+				problem("domains", format!("'{host}' is configured more than once."), Some("Every host can only belong to one domain.".to_owned()));
+			}
+
+			if domain.default_max_uses.is_some_and(|max_uses| max_uses < 0) {
+				problem("domains", format!("the `default_max_uses` of '{host}' must not be negative."), Some("Use 0 for unlimited uses.".to_owned()));
+			}
+			if domain.default_valid_for.is_some_and(|valid_for| valid_for < 0) {
+				problem("domains", format!("the `default_valid_for` of '{host}' must not be negative."), Some("It is in milliseconds, use 0 for links that never expire.".to_owned()));
+			}
+
+			hosts.push(host);
+		}
+
+		match self.frontend_location {
+			Some(ref location) => if let Some((message, suggestion)) = check_frontend_location(location) {
+				problem("frontend_location", message, suggestion);
+			},
+			#[cfg(not(feature = "integrated-frontend"))]
+			None => problem(
+				"frontend_location",
+				"is mandatory, because shorty was compiled without the `integrated-frontend` feature.".to_owned(),
+				Some("Set it to the folder containing the frontend.".to_owned()),
+			),
+			#[cfg(feature = "integrated-frontend")]
+			None => {}
+		}
+
+		if problems.is_empty() {
+			Ok(())
+		} else {
+			Err(ConfigProblems(problems))
+		}
+	}
+}
+
+/// Admin tokens shorter than this are too easy to guess.
+const MIN_ADMIN_TOKEN_LENGTH: usize = 16;
+
+type Problem = Option<(String, Option<String>)>;
+
+fn check_public_url(public_url: &str) -> Problem {
+	let url = match Url::parse(public_url) {
+		Ok(url) => url,
+		Err(url::ParseError::RelativeUrlWithoutBase) => return Some((
+			format!("'{public_url}' has no scheme."),
+			Some(format!("Try 'https://{public_url}'.")),
+		)),
+		Err(why) => return Some((format!("'{public_url}' is not a valid URL: {why}."), None)),
+	};
+
+	if !matches!(url.scheme(), "http" | "https") {
+		return Some((
+			format!("has the scheme '{}', but has to be http or https.", url.scheme()),
+			None,
+		));
+	}
+
+	if url.host().is_none() {
+		return Some((format!("'{public_url}' has no host."), None));
+	}
+
+	if public_url.ends_with('/') {
+		return Some((
+			"must not end with a slash, the links would contain two.".to_owned(),
+			Some(format!("Use '{}'.", public_url.trim_end_matches('/'))),
+		));
+	}
+
+
+	None
+}
+
+fn is_valid_scheme(scheme: &str) -> bool {
+	scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+		&& scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn check_database_location(database_location: &str) -> Problem {
+	let path = database_location.trim_start_matches("sqlite://").trim_start_matches("sqlite:");
+	if path.is_empty() {
+		return Some(("must not be empty.".to_owned(), Some(format!("The default is '{}'.", env!("DATABASE_LOCATION_DEFAULT")))));
+	}
+
+	let parent = Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty());
+	if let Some(parent) = parent {
+		if !parent.is_dir() {
+			return Some((
+				format!("is in the folder {}, which doesn't exist.", parent.display()),
+				Some("Create the folder or point it somewhere else.".to_owned()),
+			));
+		}
+	}
+
+
+	None
+}
+
+fn check_frontend_location(frontend_location: &str) -> Problem {
+	#[cfg(feature = "integrated-frontend")]
+	let fallback = Some("Remove the key to use the embedded frontend.".to_owned());
+	#[cfg(not(feature = "integrated-frontend"))]
+	let fallback = None;
+
+	if let Err(why) = std::fs::read_dir(frontend_location) {
+		return Some((format!("'{frontend_location}' can't be read: {why}."), fallback));
+	}
+
+	if !Path::new(frontend_location).join("index.html").is_file() {
+		return Some((format!("'{frontend_location}' contains no index.html."), fallback));
+	}
+
+
+	None
+}
+
+/// Returns the keys in `table` that aren't config keys, each with the most similar config key
+/// if there is one that is similar enough to be a likely typo.
+#[must_use]
+pub fn unknown_keys(table: &toml::Table) -> Vec<(&str, Option<&'static str>)> {
+	let known = known_keys();
+
+	table.keys()
+		.filter(|key| !known.contains(&key.as_str()))
+		.map(|key| {
+			let closest = known.iter()
+				.map(|known_key| (edit_distance(key, known_key), *known_key))
+				.filter(|(distance, _)| *distance <= 3)
+				.min()
+				.map(|(_, known_key)| known_key);
+
+			(key.as_str(), closest)
+		})
+		.collect()
+}
+
+/// The names of all config keys.
+/// They are taken from the [`Deserialize`] implementation of [`Config`], which passes them to the deserializer.
+fn known_keys() -> &'static [&'static str] {
+	#[derive(Debug)]
+	struct Fields(&'static [&'static str]);
+
+	impl Display for Fields {
+		fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+			write!(f, "{:?}", self.0)
+		}
+	}
+
+	impl std::error::Error for Fields {}
+
+	impl serde::de::Error for Fields {
+		fn custom<T: Display>(_: T) -> Self { Fields(&[]) }
+	}
+
+	struct FieldCollector;
+
+	impl<'de> serde::Deserializer<'de> for FieldCollector {
+		type Error = Fields;
+
+		fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+			Err(Fields(&[]))
+		}
+
+		fn deserialize_struct<V: Visitor<'de>>(
+			self,
+			_: &'static str,
+			fields: &'static [&'static str],
+			_: V,
+		) -> Result<V::Value, Self::Error> {
+			Err(Fields(fields))
+		}
+
+		serde::forward_to_deserialize_any! {
+			bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+			unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+		}
+	}
+
+	match Config::deserialize(FieldCollector) {
+		Err(Fields(fields)) => fields,
+		Ok(_) => &[],
+	}
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+	for (i, a_char) in a.chars().enumerate() {
+		let mut current = vec![i + 1];
+		for (j, b_char) in b.iter().enumerate() {
+			let substitution = previous[j] + usize::from(a_char != *b_char);
+			current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+		}
+		previous = current;
+	}
+
+
+	previous[b.len()]
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use sqlx::{Pool, Sqlite};
+use sqlx::migrate::MigrateDatabase;
+use sqlx::sqlite::{SqliteAutoVacuum, SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use tracing::info;
+
+use crate::error::ShortyError;
+
+/// Opens the database at `location`, creating the file if it doesn't exist yet.
+///
+/// # Errors
+///
+/// Errors if the database file couldn't be created or connected to.
+pub async fn connect(location: &str) -> Result<Pool<Sqlite>, ShortyError> {
+	if !Sqlite::database_exists(location).await? {
+		info!("Creating database at {location}");
+		Sqlite::create_database(location).await?;
+	}
+
+	let db_options = SqliteConnectOptions::new()
+		.auto_vacuum(SqliteAutoVacuum::Full)
+		.journal_mode(SqliteJournalMode::Wal)
+		// Aliases are deleted with their link through a foreign key.
+		.foreign_keys(true)
+		.filename(location);
+
+	let pool = SqlitePoolOptions::new()
+		.max_connections(5)
+		.min_connections(1)
+		.max_lifetime(Some(Duration::from_secs(60 * 60)))
+		.connect_with(db_options)
+		.await?;
+
+
+	Ok(pool)
+}
+
+/// Applies all pending schema migrations.
+///
+/// # Errors
+///
+/// Errors if a migration failed to apply.
+pub async fn migrate(pool: &Pool<Sqlite>) -> Result<(), ShortyError> {
+	sqlx::migrate!()
+		.run(pool)
+		.await?;
+
+
+	Ok(())
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::future::{Ready, ready};
+
+use actix_web::{FromRequest, HttpRequest};
+use actix_web::dev::Payload;
+
+use crate::config::{Config, CONFIG};
+use crate::error::ShortyError;
+
+/// A domain links get shortened on. Every domain has its own namespace of IDs.
+///
+/// The domain configured by the top level `public_url` is the default domain. Requests for a host
+/// that isn't configured in `domains` use it.
+#[derive(Debug, Clone)]
+pub struct Domain {
+	/// Identifies the domain in the database.
+	/// This is the lowercase host of the domain, or the empty string for the default domain.
+	pub name: String,
+	/// The URL prefixed to the IDs of links on this domain.
+	pub public_url: String,
+	pub default_max_uses: i64,
+	pub default_valid_for: i64,
+	/// Query parameters added to the targets of links that don't have their own.
+	pub default_append_query: BTreeMap<String, String>,
+}
+
+impl Domain {
+	/// The domain configured by the top level keys of the config.
+	#[must_use]
+	pub fn default_domain(config: &Config) -> Self {
+		Self {
+			name: String::new(),
+			public_url: config.public_url.clone(),
+			default_max_uses: config.default_max_uses,
+			default_valid_for: config.default_valid_for,
+			default_append_query: config.default_append_query.clone(),
+		}
+	}
+
+	/// The domain with the given name, if it is configured.
+	#[must_use]
+	pub fn by_name(name: &str) -> Option<Self> {
+		let config = CONFIG.load();
+		if name.is_empty() {
+			return Some(Self::default_domain(&config));
+		}
+
+		config.domains.iter()
+			.find(|domain| domain.host() == name)
+			.map(|domain| Self {
+				name: domain.host(),
+				public_url: domain.public_url.clone(),
+				default_max_uses: domain.default_max_uses.unwrap_or(config.default_max_uses),
+				default_valid_for: domain.default_valid_for.unwrap_or(config.default_valid_for),
+				default_append_query: domain.default_append_query.clone().unwrap_or_else(|| config.default_append_query.clone()),
+			})
+	}
+
+	/// The domain serving `host`, which may include a port.
+	/// Falls back to the default domain if no domain is configured for the host.
+	#[must_use]
+	pub fn for_host(host: &str) -> Self {
+		let host = strip_port(host).to_lowercase();
+
+
+		Self::by_name(host.as_str())
+			.unwrap_or_else(|| Self::default_domain(&CONFIG.load()))
+	}
+
+	/// Resolves a domain that was explicitly asked for, e.g. in a [`LinkConfig`](crate::link::LinkConfig).
+	///
+	/// # Errors
+	///
+	/// Errors if the domain isn't configured.
+	pub fn requested(name: &str) -> Result<Self, ShortyError> {
+		Self::by_name(strip_port(name).to_lowercase().as_str())
+			.ok_or_else(|| ShortyError::UnknownDomain(name.to_owned()))
+	}
+
+	/// Resolves a domain that may have been asked for, or the default domain if none was.
+	///
+	/// # Errors
+	///
+	/// Errors if the requested domain isn't configured.
+	pub fn requested_or_default(name: Option<&str>) -> Result<Self, ShortyError> {
+		match name {
+			Some(name) => Self::requested(name),
+			None => Ok(Self::default_domain(&CONFIG.load())),
+		}
+	}
+}
+
+/// Resolves the domain from the host the request was sent to.
+/// This respects the `Forwarded` and `X-Forwarded-Host` headers set by reverse proxies.
+impl FromRequest for Domain {
+	type Error = ShortyError;
+	type Future = Ready<Result<Self, Self::Error>>;
+
+	fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+		ready(Ok(Domain::for_host(req.connection_info().host())))
+	}
+}
+
+/// Removes the port from a host, taking care of IPv6 addresses like `[::1]:7999`.
+fn strip_port(host: &str) -> &str {
+	match host.rsplit_once(':') {
+		Some((without_port, port)) if !port.contains(']') && port.chars().all(|c| c.is_ascii_digit()) => without_port,
+		_ => host,
+	}
+}
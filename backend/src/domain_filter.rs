@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use url::{Host, Url};
+
+use crate::config::Config;
+use crate::error::ShortyError;
+
+/// Decides which domains links may redirect to, based on the `allowed_domains` and `blocked_domains`
+/// config keys and the files referenced by `allowed_domains_file` and `blocked_domains_file`.
+///
+/// Blocked domains always win. If any domain is allowed, every domain that isn't allowed is blocked.
+#[derive(Debug, Default, Clone)]
+pub struct DomainFilter {
+	allowed: Vec<DomainPattern>,
+	blocked: Vec<DomainPattern>,
+}
+
+impl DomainFilter {
+	/// Builds the filter from the lists in the config and reads the list files.
+	///
+	/// # Errors
+	///
+	/// Errors if a list file can't be read.
+	pub fn load(config: &Config) -> Result<Self, ShortyError> {
+		let mut allowed = parse_patterns(config.allowed_domains.iter().map(String::as_str));
+		let mut blocked = parse_patterns(config.blocked_domains.iter().map(String::as_str));
+
+		if let Some(ref file) = config.allowed_domains_file {
+			allowed.extend(parse_patterns(read_list(file)?.iter().map(String::as_str)));
+		}
+		if let Some(ref file) = config.blocked_domains_file {
+			blocked.extend(parse_patterns(read_list(file)?.iter().map(String::as_str)));
+		}
+
+
+		Ok(Self { allowed, blocked })
+	}
+
+	/// Checks whether links may redirect to `url`.
+	///
+	/// # Errors
+	///
+	/// Errors with [`ShortyError::BlockedDomain`] if the host of the URL is blocked or not allowed.
+	pub fn check(&self, url: &str) -> Result<(), ShortyError> {
+		let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_lowercase)) else {
+			// URLs without a host, like `mailto:`, have no domain to filter by.
+			return Ok(());
+		};
+		let host = host.trim_end_matches('.');
+
+		let blocked = self.blocked.iter().any(|pattern| pattern.matches(host));
+		let allowed = self.allowed.is_empty() || self.allowed.iter().any(|pattern| pattern.matches(host));
+		if blocked || !allowed {
+			return Err(ShortyError::BlockedDomain(host.to_owned()));
+		}
+
+
+		Ok(())
+	}
+}
+
+/// A domain in an allow or block list.
+/// `example.com` only matches that exact domain, `*.example.com` matches all of its subdomains, but not itself.
+#[derive(Debug, Clone)]
+struct DomainPattern {
+	domain: String,
+	subdomains: bool,
+}
+
+impl DomainPattern {
+	/// Parses a pattern, converting internationalized domain names to punycode.
+	fn parse(pattern: &str) -> Result<Self, String> {
+		let pattern = pattern.trim().trim_end_matches('.');
+		let (domain, subdomains) = match pattern.strip_prefix("*.") {
+			Some(domain) => (domain, true),
+			None => (pattern, false),
+		};
+
+		if domain.contains('*') {
+			return Err(format!("'{pattern}' can only contain a wildcard at the start, like '*.example.com'."));
+		}
+
+		match Host::parse(domain) {
+			Ok(Host::Domain(domain)) => Ok(Self { domain, subdomains }),
+			Ok(_) if subdomains => Err(format!("'{pattern}' is an IP address, which has no subdomains.")),
+			Ok(host) => Ok(Self { domain: host.to_string(), subdomains }),
+			Err(why) => Err(format!("'{pattern}' is not a valid domain: {why}.")),
+		}
+	}
+
+	fn matches(&self, host: &str) -> bool {
+		if self.subdomains {
+			host.strip_suffix(self.domain.as_str()).is_some_and(|rest| rest.ends_with('.'))
+		} else {
+			host == self.domain
+		}
+	}
+}
+
+/// Parses the patterns that are valid. Invalid ones are reported by [`check_patterns`] during validation.
+fn parse_patterns<'a>(patterns: impl Iterator<Item = &'a str>) -> Vec<DomainPattern> {
+	patterns
+		.filter_map(|pattern| DomainPattern::parse(pattern).ok())
+		.collect()
+}
+
+/// Reads the domains from a list file, one per line. Empty lines and lines starting with `#` are ignored.
+fn read_list(file: &str) -> Result<Vec<String>, std::io::Error> {
+	let content = std::fs::read_to_string(Path::new(file))?;
+
+
+	Ok(
+		content.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(str::to_owned)
+			.collect()
+	)
+}
+
+/// Returns a message for every pattern that isn't valid.
+#[must_use]
+pub fn check_patterns<'a>(patterns: impl Iterator<Item = &'a str>) -> Vec<String> {
+	patterns
+		.filter_map(|pattern| DomainPattern::parse(pattern).err())
+		.collect()
+}
+
+/// Returns a message for every problem with a list file, including invalid patterns in it.
+#[must_use]
+pub fn check_list_file(file: &str) -> Vec<String> {
+	match read_list(file) {
+		Ok(patterns) => check_patterns(patterns.iter().map(String::as_str)),
+		Err(why) => vec![format!("'{file}' can't be read: {why}.")],
+	}
+}
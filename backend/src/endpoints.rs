@@ -1,14 +1,26 @@
 use actix_files::NamedFile;
 use actix_web::{get, HttpRequest, HttpResponse, post, Responder, web};
+use chrono::{TimeZone, Utc};
 use tracing::{debug, info};
-use utoipa::OpenApi;
+use utoipa::{Modify, OpenApi};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
 
+use crate::admin;
 use crate::CONFIG;
 use crate::config::Config;
+use crate::domain::Domain;
+use crate::importers::Source;
 use crate::error::ShortyError;
 use crate::LinkConfig;
 use crate::LinkStore;
-use crate::util::uri_to_url;
+use crate::link::{BatchResult, Link, LinkState, Lookup, Totals};
+use crate::redirect::{location, redirect, rule_target, temporary_redirect};
+use crate::rules::{Platform, Rule, Weekday};
+use crate::alias::{Alias, AliasRequest};
+use crate::variant::{sticky_cookie, sticky_position, Variant, VariantConfig};
+use crate::report::{Report, ReportRequest};
+use crate::transfer::{ConflictPolicy, ExportedLink, Format, ImportOutcome, ImportResult};
+use crate::util::{client_address, uri_to_url};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -17,21 +29,51 @@ use crate::util::uri_to_url;
 		get_config,
 		create_shortened,
 		create_shortened_custom,
+		create_shortened_batch,
+		report_link,
+		admin::list_links,
+		admin::get_link_history,
+		admin::get_link_variants,
+		admin::get_link_aliases,
+		admin::add_link_alias,
+		admin::remove_link_alias,
+		admin::export_links,
+		admin::import_links,
+		admin::get_totals,
+		admin::disable_link,
+		admin::delete_link,
+		admin::list_reports,
+		admin::reinstate_link,
+		admin::ban_link,
 	),
+	components(schemas(Report, admin::AdminLink, admin::LinkPage, LinkState, Totals, Rule, Platform, Weekday, Variant, VariantConfig, Alias, AliasRequest, BatchResult, ExportedLink, ImportResult, ImportOutcome, Format, ConflictPolicy, Source)),
+	modifiers(&ApiTokenSecurity),
 	tags(
 		(name = "/", description = "Simple shortening"),
 		(name = "/custom", description = "Advanced shortening"),
 		(name = "/config", description = "Server configuration"),
+		(name = "/admin", description = "Instance administration, requires an API token"),
 	)
 )]
 pub struct ApiDoc;
 
+/// Documents the bearer token the admin endpoints require, so it can be entered in the Swagger UI.
+struct ApiTokenSecurity;
+
+impl Modify for ApiTokenSecurity {
+	fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+		if let Some(components) = openapi.components.as_mut() {
+			components.add_security_scheme("api_token", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+		}
+	}
+}
+
 // The function is async because the actix-web macro requires it.
 #[allow(clippy::unused_async)]
 #[get("/")]
 pub async fn index(req: HttpRequest) -> Result<impl Responder, Box<dyn std::error::Error>> {
 	debug!("Got request for Index");
-	if let Some(ref path) = CONFIG.frontend_location {
+	if let Some(ref path) = CONFIG.load().frontend_location {
 		let path = format!("{path}/index.html");
 		return Ok(NamedFile::open(path)?.into_response(&req));
 	}
@@ -57,32 +99,219 @@ pub async fn index(req: HttpRequest) -> Result<impl Responder, Box<dyn std::erro
 	params((
 		"link_id" = inline(String),
 		Path,
-		description = "The id of the aliased url",
+		description = "The id of the aliased url. For prefix and template links it can be followed by more path, which is passed on together with the query",
 	)),
 	responses(
-		(status = 307, description = "Redirection to aliased url"),
-		(status = 404, description = "Shortened ID couldn't be found or was expired"),
+		(status = 307, description = "Redirection to aliased url, with the redirect type of the link (301, 302, 307 or 308)"),
+		(status = 403, description = "The domain of the aliased url is blocked or banned"),
+		(status = 404, description = "Shortened ID couldn't be found or isn't available yet, responds with a page explaining that", content_type = "text/html"),
+		(status = 410, description = "The link expired or was disabled, responds with a page explaining why", content_type = "text/html"),
 	),
 )]
 #[get("/{link_id:.*}")]
 async fn get_shortened(
+	req: HttpRequest,
 	params: web::Path<String>,
+	domain: Domain,
 	link_store: web::Data<LinkStore>,
 ) -> Result<impl Responder, ShortyError> {
 	let link_id = params.into_inner();
 	debug!("Got request for {link_id}");
 
 
-	if let Some(link) = link_store.get(link_id.as_str()).await {
-		info!("Return url for {link_id} is {link}");
-		Ok(
-			HttpResponse::TemporaryRedirect()
-				.append_header(("Location", link.redirect_to.as_str()))
-				.finish()
-		)
-	} else {
-		Ok(HttpResponse::NotFound().finish())
+	match link_store.get(&domain, link_id.as_str()).await? {
+		Lookup::Active(link, rest) => {
+			// Visitors matching a rule always follow it, the others are split between the variants.
+			let (base, variant) = match rule_target(&link, &req) {
+				Some(target) => (target, None),
+				None => match link_store.choose_variant(&link, sticky_position(&req, &link)).await? {
+					Some(variant) => (variant.redirect_to.clone(), Some(variant)),
+					None => (link.redirect_to.clone(), None),
+				},
+			};
+			let location = location(&link, &domain, base.as_str(), rest.as_str(), &req);
+			// The domain might have been blocked after the link was created.
+			// Visitors that aren't redirected because of that don't use up the link or count for the variant.
+			link_store.check_target(location.as_str()).await?;
+			link_store.count_use(&link, variant.as_ref()).await?;
+			info!("Return url for {link_id} is {location}");
+
+			let mut response = redirect(&link, location.as_str());
+			if let Some(variant) = variant.filter(|_| link.sticky) {
+				response.cookie(sticky_cookie(&req, &link, &variant));
+			}
+
+
+			Ok(response.finish())
+		}
+		Lookup::Pending(link) => match link.not_before_redirect_to {
+			Some(ref target) => {
+				link_store.check_target(target.as_str()).await?;
+				Ok(temporary_redirect(target.as_str()))
+			}
+			None => Ok(dead_link_page(&DeadLink::NotYetAvailable(link.starts_at()), &domain, link_id.as_str())),
+		},
+		Lookup::Gone(link) => Ok(dead_link_page(&DeadLink::from(&link), &domain, link_id.as_str())),
+		Lookup::NotFound => Ok(dead_link_page(&DeadLink::Unknown, &domain, link_id.as_str())),
+	}
+}
+
+/// Where the page for links that can't be opened is, relative to the frontend.
+const LINK_ERROR_PAGE: &str = "pages/link-error.html";
+
+/// Used if neither the frontend nor the embedded files contain a page for links that can't be opened.
+const FALLBACK_LINK_ERROR_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="UTF-8"><title>{{title}}</title></head>
+<body><h1>{{title}}</h1><p>{{message}}</p><p><a href="{{home}}">Shorten a link yourself</a></p></body>
+</html>"#;
+
+/// Why a link can't be opened.
+enum DeadLink {
+	Unknown,
+	/// The link goes live at this point in time, in milliseconds.
+	NotYetAvailable(i64),
+	ExpiredByTime,
+	OutOfUses,
+	Disabled,
+}
+
+impl From<&Link> for DeadLink {
+	fn from(link: &Link) -> Self {
+		match link.state() {
+			LinkState::Disabled => DeadLink::Disabled,
+			LinkState::Deleted => DeadLink::Unknown,
+			_ if link.max_uses > 0 && link.invocations >= link.max_uses => DeadLink::OutOfUses,
+			_ => DeadLink::ExpiredByTime,
+		}
+	}
+}
+
+/// Replaces the `{{name}}` placeholders of the page with their values in a single pass.
+/// HTML comments are left as they are, so placeholders mentioned in them don't give the values away.
+fn fill_placeholders(page: &str, values: &[(&str, &str)]) -> String {
+	let mut filled = String::with_capacity(page.len());
+	let mut rest = page;
+	loop {
+		let comment = rest.find("<!--");
+		let placeholder = rest.find("{{");
+		match (comment, placeholder) {
+			(Some(comment), placeholder) if placeholder.is_none_or(|placeholder| comment < placeholder) => {
+				let end = rest[comment..].find("-->").map_or(rest.len(), |end| comment + end + "-->".len());
+				filled.push_str(&rest[..end]);
+				rest = &rest[end..];
+			}
+			(_, Some(placeholder)) => {
+				filled.push_str(&rest[..placeholder]);
+				rest = &rest[placeholder..];
+				let replaced = values.iter().find_map(|(name, value)| {
+					rest.strip_prefix(format!("{{{{{name}}}}}").as_str()).map(|after| (*value, after))
+				});
+				let (value, after) = replaced.unwrap_or(("{{", &rest[2..]));
+				filled.push_str(value);
+				rest = after;
+			}
+			(_, None) => {
+				filled.push_str(rest);
+				break;
+			}
+		}
 	}
+
+
+	filled
+}
+
+/// Renders the page explaining why the link can't be opened.
+/// The page is taken from the `frontend_location` if it has one, otherwise the embedded one is used.
+fn dead_link_page(reason: &DeadLink, domain: &Domain, link_id: &str) -> HttpResponse {
+	let url = format!("{}/{link_id}", domain.public_url);
+	let (mut response, title, message) = match reason {
+		DeadLink::Unknown => (HttpResponse::NotFound(), "Link not found", format!("There is no link at {url}.")),
+		DeadLink::NotYetAvailable(not_before) => {
+			let not_before = Utc.timestamp_millis_opt(*not_before)
+				.single()
+				.map_or_else(|| "later".to_owned(), |time| time.format("on %Y-%m-%d at %H:%M UTC").to_string());
+			(HttpResponse::NotFound(), "Link not available yet", format!("The link {url} will be available {not_before}."))
+		}
+		DeadLink::ExpiredByTime => (HttpResponse::Gone(), "Link expired", format!("The link {url} was only valid for a limited time, which is over.")),
+		DeadLink::OutOfUses => (HttpResponse::Gone(), "Link used up", format!("The link {url} could only be used a limited number of times, which have all been used.")),
+		DeadLink::Disabled => (HttpResponse::Gone(), "Link disabled", format!("The link {url} was disabled.")),
+	};
+
+	let template = link_error_template().unwrap_or_else(|| FALLBACK_LINK_ERROR_PAGE.to_owned());
+	let message = escape_html(message.as_str());
+	let home = escape_html(format!("{}/", domain.public_url).as_str());
+	let page = fill_placeholders(template.as_str(), &[("title", title), ("message", message.as_str()), ("home", home.as_str())]);
+
+
+	response
+		.content_type("text/html; charset=utf-8")
+		.body(page)
+}
+
+fn link_error_template() -> Option<String> {
+	if let Some(ref path) = CONFIG.load().frontend_location {
+		if let Ok(page) = std::fs::read_to_string(format!("{path}/{LINK_ERROR_PAGE}")) {
+			return Some(page);
+		}
+	}
+
+	#[cfg(feature = "integrated-frontend")]
+	if let Some((_, page)) = get_embedded_file(LINK_ERROR_PAGE) {
+		return Some(String::from_utf8_lossy(page).into_owned());
+	}
+
+
+	None
+}
+
+/// Escapes text so it can be put into HTML, IDs can contain anything.
+fn escape_html(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&#39;")
+}
+
+/// Report a link
+///
+/// Reports a link that leads somewhere malicious, e.g. to a phishing site.
+/// The link gets disabled until an admin reviews it once enough different people reported it.
+#[utoipa::path(
+	tag = "/",
+	params((
+		"link_id" = inline(String),
+		Path,
+		description = "The id of the reported link",
+	)),
+	request_body(content = inline(ReportRequest), description = "Why the link is reported"),
+	responses(
+		(status = 202, description = "The report was received"),
+		(status = 400, description = "The reason is empty or too long"),
+		(status = 404, description = "Shortened ID couldn't be found"),
+	),
+)]
+#[post("/report/{link_id}")]
+async fn report_link(
+	req: HttpRequest,
+	params: web::Path<String>,
+	domain: Domain,
+	link_store: web::Data<LinkStore>,
+	report: web::Json<ReportRequest>,
+) -> Result<impl Responder, ShortyError> {
+	let link_id = params.into_inner();
+	let Some(link) = link_store.find(&domain, link_id.as_str()).await? else {
+		return Ok(HttpResponse::NotFound().finish());
+	};
+
+	let reporter = client_address(&req, &CONFIG.load()).map_or_else(|| "unknown".to_owned(), |address| address.to_string());
+	link_store.report(&link, report.reason.as_str(), reporter.as_str()).await?;
+	info!("{} was reported: {}", link.formatted(), report.reason.trim());
+
+
+	Ok(HttpResponse::Accepted().finish())
 }
 
 /// Retrieves the servers configuration details
@@ -98,7 +327,7 @@ async fn get_shortened(
 async fn get_config() -> impl Responder {
 	HttpResponse::Ok()
 		.content_type("application/json; charset=utf-8")
-		.body(CONFIG.json_string())
+		.body(CONFIG.load().json_string())
 }
 
 /// Create a simple, unconfigured shortened link.
@@ -111,19 +340,22 @@ async fn get_config() -> impl Responder {
 	)),
 	responses(
 		(status = 200, description = "The url was successfully shortened"),
+		(status = 400, description = "The url is invalid, has a scheme that isn't allowed, contains credentials or points back at the shortener"),
+		(status = 403, description = "The domain of the url is blocked"),
 	),
 )]
 #[post("/{url:.*}")]
 #[allow(clippy::similar_names)]
 async fn create_shortened(
 	req: HttpRequest,
+	domain: Domain,
 	link_store: web::Data<LinkStore>,
 ) -> Result<impl Responder, ShortyError> {
 	let uri = req.uri();
 	debug!("URI is {uri}");
 	let url = uri_to_url(uri);
 
-	let link = link_store.create_link(url).await?;
+	let link = link_store.create_link(url, &domain).await?;
 	let formatted = link.formatted();
 	info!("Shortening URL {} to {}", link.redirect_to, formatted);
 
@@ -143,18 +375,20 @@ async fn create_shortened(
 	request_body(content = inline(LinkConfig), description = "The settings for the url to alias"),
 	responses(
 		(status = 200, description = "The url was successfully registered as an alias and is now retrievable with at the get endpoint"),
-		(status = 400, description = "Json is malformed, the link exceeds the max length allowed by the server or the link was empty"),
-		(status = 409, description = "The specified ID is already in use"),
+		(status = 400, description = "Json is malformed, the link exceeds the max length allowed by the server, the link was empty or invalid or the domain isn't configured"),
+		(status = 403, description = "The domain of the link is blocked"),
+		(status = 409, description = "The specified ID, or one that is the same after normalization, is already in use or expired too recently"),
 	),
 )]
 #[post("/custom")]
 async fn create_shortened_custom(
+	domain: Domain,
 	link_store: web::Data<LinkStore>,
 	link_config: web::Json<LinkConfig>,
 ) -> Result<impl Responder, ShortyError> {
 	let link_config = link_config.into_inner();
 
-	let link = link_store.create_link_with_config(link_config).await?;
+	let link = link_store.create_link_with_config(link_config, &domain).await?;
 	let formatted = link.formatted();
 	info!("Shortening URL {} to {}", link.redirect_to, formatted);
 
@@ -166,6 +400,43 @@ async fn create_shortened_custom(
 	)
 }
 
+/// Bulk url shortening
+///
+/// Shortens up to `max_batch_size` URLs at once in a single transaction, with the same settings as `/custom`.
+/// Links that can't be created don't keep the others from being created,
+/// the result of every link is returned in the order of the request.
+#[utoipa::path(
+	tag = "/custom",
+	request_body(content = inline(Vec<LinkConfig>), description = "The settings for every url to shorten"),
+	responses(
+		(status = 200, body = [BatchResult], description = "The result for every url, in the order of the request"),
+		(status = 400, description = "Json is malformed"),
+		(status = 413, description = "The batch contains more than `max_batch_size` links or exceeds `max_json_size`"),
+	),
+)]
+#[post("/custom/batch")]
+async fn create_shortened_batch(
+	domain: Domain,
+	link_store: web::Data<LinkStore>,
+	link_configs: web::Json<Vec<LinkConfig>>,
+) -> Result<impl Responder, ShortyError> {
+	let link_configs = link_configs.into_inner();
+	let max_batch_size = CONFIG.load().max_batch_size;
+	if link_configs.len() > max_batch_size {
+		return Err(ShortyError::BatchTooLarge(max_batch_size));
+	}
+
+	let results: Vec<BatchResult> = link_store.create_links_with_config(link_configs, &domain).await?
+		.into_iter()
+		.map(BatchResult::from)
+		.collect();
+	let created = results.iter().filter(|result| result.url.is_some()).count();
+	info!("Shortened {created} of {} URLs in a batch", results.len());
+
+
+	Ok(HttpResponse::Ok().json(results))
+}
+
 #[allow(clippy::unused_async)]
 #[get("/favicon.ico")]
 async fn get_favicon() -> Result<impl Responder, ShortyError> {
@@ -180,7 +451,7 @@ pub async fn serve_file(asset: web::Path<String>, req: HttpRequest) -> Result<im
 	let asset = asset.into_inner();
 	debug!("Got request for file: {asset}");
 
-	if let Some(ref path) = CONFIG.frontend_location {
+	if let Some(ref path) = CONFIG.load().frontend_location {
 		let path = format!("{path}/{asset}");
 		return Ok(NamedFile::open(path)?.into_response(&req));
 	}
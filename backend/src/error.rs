@@ -3,33 +3,108 @@ use actix_web::body::BoxBody;
 use actix_web::http::StatusCode;
 use thiserror::Error;
 
+use crate::config::Role;
+use crate::config_validation::ConfigProblems;
+
 #[derive(Debug, Error)]
 pub enum ShortyError {
 	#[error("Link with provided ID already exists")]
 	LinkConflict,
+	#[error("The ID is too similar to the existing ID {0}")]
+	NormalizedIdConflict(String),
+	#[error("The ID {0} expired recently and can't be used again yet")]
+	IdRecentlyExpired(String),
 	#[error("Link exceeds maximum length allowed.")]
 	LinkExceedsMaxLength,
 	#[error("Custom ID exceeds maximum length allowed.")]
 	CustomIDExceedsMaxLength,
 	#[error("Link is empty.")]
 	LinkEmpty,
+	#[error("Link is not a valid URL: {0}.")]
+	InvalidUrl(String),
+	#[error("Links with the scheme {0} are not allowed.")]
+	DisallowedScheme(String),
+	#[error("Links must not contain a username or password.")]
+	CredentialsInUrl,
+	#[error("Links must not point back at the shortener.")]
+	RedirectLoop,
+	#[error("Links to {0} are not allowed.")]
+	BlockedDomain(String),
+	#[error("The reason of a report must not be empty or longer than {} characters.", crate::report::MAX_REASON_LENGTH)]
+	InvalidReportReason,
+	#[error("A valid admin token is required.")]
+	Unauthorized,
+	#[error("The token lacks the {0} role.")]
+	MissingRole(Role),
+	#[error("{0} is not a redirect status code, use 301, 302, 307 or 308.")]
+	InvalidRedirectType(i64),
+	#[error("The link is not a valid template: {0}.")]
+	InvalidTemplate(String),
+	#[error("The rules of the link are invalid: {0}.")]
+	InvalidRule(String),
+	#[error("The variants of the link are invalid: {0}.")]
+	InvalidVariants(String),
+	#[error("A link can't be a prefix and a template link at the same time.")]
+	PrefixTemplate,
+	#[error("A batch can contain at most {0} links.")]
+	BatchTooLarge(usize),
 	#[error("Maximum retries to generate a random link ID were exceeded.")]
 	RandomIDMaxRetriesExceeded,
 	#[error("An already expired Link was provided.")]
 	ExpiredLinkProvided,
+	#[error("The domain {0} is not configured.")]
+	UnknownDomain(String),
+	#[error("The link {0} is banned, bans can't be lifted.")]
+	LinkBanned(String),
+	#[error("There is no link with the ID {0}.")]
+	LinkNotFound(String),
+	#[error("The link {0} has no alias {1}.")]
+	AliasNotFound(String, String),
+	#[error("{0} exists already, use --force to overwrite it.")]
+	ConfigExists(String),
 	#[error(transparent)]
 	Database(#[from] sqlx::Error),
 	#[error(transparent)]
 	Dotenvy(#[from] dotenvy::Error),
+	#[error("Failed to listen on {0}: {1}")]
+	Bind(String, std::io::Error),
+	#[error(transparent)]
+	Io(#[from] std::io::Error),
+	#[error(transparent)]
+	Csv(#[from] csv::Error),
+	#[error("Failed to parse config: {0}")]
+	ConfigParse(#[from] toml::de::Error),
+	#[error(transparent)]
+	InvalidConfig(#[from] ConfigProblems),
+	#[error(transparent)]
+	Migration(#[from] sqlx::migrate::MigrateError),
 }
 
 impl ResponseError for ShortyError {
 	fn status_code(&self) -> StatusCode {
 		match self {
-			ShortyError::LinkConflict => StatusCode::CONFLICT,
+			ShortyError::LinkConflict
+			| ShortyError::NormalizedIdConflict(_)
+			| ShortyError::IdRecentlyExpired(_)
+			| ShortyError::LinkBanned(_) => StatusCode::CONFLICT,
+			ShortyError::BlockedDomain(_) | ShortyError::MissingRole(_) => StatusCode::FORBIDDEN,
+			ShortyError::Unauthorized => StatusCode::UNAUTHORIZED,
+			ShortyError::LinkNotFound(_) | ShortyError::AliasNotFound(..) => StatusCode::NOT_FOUND,
+			ShortyError::BatchTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
 			ShortyError::LinkExceedsMaxLength
 			| ShortyError::LinkEmpty
+			| ShortyError::InvalidUrl(_)
+			| ShortyError::DisallowedScheme(_)
+			| ShortyError::CredentialsInUrl
+			| ShortyError::RedirectLoop
+			| ShortyError::InvalidReportReason
+			| ShortyError::InvalidRedirectType(_)
+			| ShortyError::InvalidTemplate(_)
+			| ShortyError::PrefixTemplate
+			| ShortyError::InvalidRule(_)
+			| ShortyError::InvalidVariants(_)
 			| ShortyError::ExpiredLinkProvided
+			| ShortyError::UnknownDomain(_)
 			| ShortyError::CustomIDExceedsMaxLength => StatusCode::BAD_REQUEST,
 			_ => StatusCode::INTERNAL_SERVER_ERROR,
 		}
@@ -0,0 +1,726 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::link::{DISABLED_BANNED, LinkState};
+use crate::transfer::ExportedLink;
+use crate::util::time_now;
+
+/// The columns of the YOURLS URL table, in the order of its `CREATE TABLE`.
+const YOURLS_COLUMNS: [&str; 6] = ["keyword", "url", "title", "timestamp", "ip", "clicks"];
+
+/// Other link shorteners whose exports can be imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+	/// A SQL dump of the YOURLS URL table, or a CSV export of it with a header row.
+	Yourls,
+	/// The JSON the Kutt API lists links with, either the whole response or just its `data` array.
+	Kutt,
+	/// A CSV export of Bitly links with a header row.
+	Bitly,
+}
+
+/// Reads the links of an export of another link shortener, with the line each of them starts on,
+/// or their position in the array for JSON. The links are put on the domain with the host `domain`.
+/// Links that can't be read are returned as errors, so the others can still be imported.
+#[must_use]
+pub fn parse(source: Source, input: &[u8], domain: &str) -> Vec<(u64, Result<ExportedLink, String>)> {
+	let links = match source {
+		Source::Yourls if is_sql_dump(input) => match std::str::from_utf8(input) {
+			Ok(input) => yourls_sql(input),
+			Err(why) => vec![(1, Err(why.to_string()))],
+		},
+		Source::Yourls => csv_rows(input).into_iter()
+			.map(|(line, row)| (line, row.and_then(|row| yourls_link(&row))))
+			.collect(),
+		Source::Kutt => kutt(input),
+		Source::Bitly => csv_rows(input).into_iter()
+			.map(|(line, row)| (line, row.and_then(|row| bitly_link(&row))))
+			.collect(),
+	};
+
+
+	links.into_iter()
+		.map(|(line, link)| (line, link.map(|link| ExportedLink { domain: domain.to_owned(), ..link })))
+		.collect()
+}
+
+/// A link with only what the other link shorteners have in common, permanent and without limits.
+fn link(id: &str, redirect_to: &str, clicks: Option<&str>, created_at: Option<&str>) -> Result<ExportedLink, String> {
+	if id.trim().is_empty() {
+		return Err("the link has no ID".to_owned());
+	}
+	if redirect_to.trim().is_empty() {
+		return Err(format!("the link {id} has no target"));
+	}
+	let invocations = match clicks.map(str::trim).filter(|clicks| !clicks.is_empty()) {
+		Some(clicks) => clicks.parse().map_err(|_| format!("the link {id} has an invalid click count: {clicks}"))?,
+		None => 0,
+	};
+
+
+	Ok(ExportedLink {
+		domain: String::new(),
+		id: id.trim().to_owned(),
+		redirect_to: redirect_to.trim().to_owned(),
+		max_uses: 0,
+		invocations,
+		created_at: created_at.and_then(parse_timestamp),
+		valid_for: 0,
+		redirect_type: None,
+		prefix: false,
+		template: false,
+		append_query: None,
+		rules: None,
+		not_before: None,
+		not_before_redirect_to: None,
+		status: LinkState::Active,
+		disabled_reason: None,
+		status_changed_at: None,
+	})
+}
+
+/// Parses the timestamps the other link shorteners export, in milliseconds.
+/// Timestamps without a time zone are taken as local time, numbers as seconds or milliseconds since the unix epoch.
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+	let timestamp = timestamp.trim();
+	if let Ok(number) = timestamp.parse::<i64>() {
+		// Seconds would only be this large in the year 5138.
+		return Some(if number < 100_000_000_000 { number * 1000 } else { number });
+	}
+	if let Ok(time) = DateTime::parse_from_rfc3339(timestamp) {
+		return Some(time.timestamp_millis());
+	}
+	for format in ["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f %z"] {
+		if let Ok(time) = DateTime::parse_from_str(timestamp, format) {
+			return Some(time.timestamp_millis());
+		}
+	}
+
+	let naive = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"].into_iter()
+		.find_map(|format| NaiveDateTime::parse_from_str(timestamp, format).ok())
+		.or_else(|| NaiveDate::parse_from_str(timestamp, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))?;
+
+
+	naive.and_local_timezone(Local).earliest().map(|time| time.timestamp_millis())
+}
+
+/// The values of a CSV row or an inserted SQL row by their column.
+type Row = HashMap<String, String>;
+
+/// Reads the rows of a CSV file with a header row. The column names are normalized by [`column_name`].
+fn csv_rows(input: &[u8]) -> Vec<(u64, Result<Row, String>)> {
+	let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(input);
+	let headers = match reader.headers() {
+		Ok(headers) => headers.iter().map(column_name).collect::<Vec<_>>(),
+		Err(why) => return vec![(1, Err(why.to_string()))],
+	};
+
+	reader.records()
+		.map(|record| match record {
+			Ok(record) => {
+				let line = record.position().map_or(0, csv::Position::line);
+				let row = headers.iter().cloned().zip(record.iter().map(str::to_owned)).collect();
+				(line, Ok(row))
+			}
+			Err(why) => (why.position().map_or(0, csv::Position::line), Err(why.to_string())),
+		})
+		.collect()
+}
+
+/// Normalizes a column name, so `Long URL`, `long-url` and `long_url` are all `long_url`.
+fn column_name(name: &str) -> String {
+	name.trim()
+		.to_lowercase()
+		.split(|c: char| !c.is_alphanumeric())
+		.filter(|part| !part.is_empty())
+		.collect::<Vec<_>>()
+		.join("_")
+}
+
+/// The first non-empty value of the columns.
+fn field<'a>(row: &'a Row, columns: &[&str]) -> Option<&'a str> {
+	columns.iter()
+		.filter_map(|column| row.get(*column))
+		.map(String::as_str)
+		.find(|value| !value.trim().is_empty())
+}
+
+fn yourls_link(row: &Row) -> Result<ExportedLink, String> {
+	link(
+		field(row, &["keyword"]).unwrap_or_default(),
+		field(row, &["url"]).unwrap_or_default(),
+		field(row, &["clicks"]),
+		field(row, &["timestamp"]),
+	)
+}
+
+fn bitly_link(row: &Row) -> Result<ExportedLink, String> {
+	// A custom back-half is the ID people know the link by, the random one is only used without it.
+	let short = field(row, &["custom_bitlink", "custom_bitlinks", "custom_back_half", "bitlink", "short_url", "link", "id"])
+		.unwrap_or_default();
+	let short = short.split([',', ' ']).find(|short| !short.is_empty()).unwrap_or_default();
+	let id = short.split('?').next().unwrap_or_default()
+		.trim_end_matches('/')
+		.rsplit('/')
+		.next()
+		.unwrap_or_default();
+
+
+	link(
+		id,
+		field(row, &["long_url", "destination", "destination_url", "original_url"]).unwrap_or_default(),
+		field(row, &["clicks", "total_clicks", "click_count", "engagements"]),
+		field(row, &["created_at", "date_created", "created", "creation_date"]),
+	)
+}
+
+/// A link as the Kutt API lists it.
+#[derive(Debug, Deserialize)]
+struct KuttLink {
+	address: String,
+	target: String,
+	#[serde(default)]
+	visit_count: Option<serde_json::Value>,
+	#[serde(default)]
+	created_at: Option<String>,
+	#[serde(default)]
+	expire_in: Option<String>,
+	/// A boolean in the API, the hash in dumps of the database.
+	#[serde(default)]
+	password: Option<serde_json::Value>,
+	#[serde(default)]
+	banned: bool,
+}
+
+fn kutt(input: &[u8]) -> Vec<(u64, Result<ExportedLink, String>)> {
+	let value = match serde_json::from_slice::<serde_json::Value>(input) {
+		Ok(value) => value,
+		Err(why) => return vec![(1, Err(why.to_string()))],
+	};
+	let links = match value {
+		serde_json::Value::Array(links) => links,
+		serde_json::Value::Object(mut response) => match response.remove("data") {
+			Some(serde_json::Value::Array(links)) => links,
+			_ => return vec![(1, Err("expected an array of links or an object with one as `data`".to_owned()))],
+		},
+		_ => return vec![(1, Err("expected an array of links or an object with one as `data`".to_owned()))],
+	};
+
+	links.into_iter()
+		.zip(1..)
+		.map(|(link, position)| (position, serde_json::from_value(link).map_err(|why| why.to_string()).and_then(kutt_link)))
+		.collect()
+}
+
+fn kutt_link(kutt: KuttLink) -> Result<ExportedLink, String> {
+	let protected = match &kutt.password {
+		Some(serde_json::Value::Bool(protected)) => *protected,
+		Some(serde_json::Value::String(hash)) => !hash.is_empty(),
+		_ => false,
+	};
+	if protected {
+		// Importing it would make the target public.
+		return Err(format!("the link {} is password protected, which shorty doesn't support", kutt.address));
+	}
+
+	let clicks = kutt.visit_count.as_ref().map(|count| match count {
+		serde_json::Value::String(count) => count.clone(),
+		count => count.to_string(),
+	});
+	let mut link = link(kutt.address.as_str(), kutt.target.as_str(), clicks.as_deref(), kutt.created_at.as_deref())?;
+	let expires_at = kutt.expire_in.as_deref().and_then(parse_timestamp);
+	if let Some(expires_at) = expires_at {
+		let created_at = link.created_at.get_or_insert_with(time_now);
+		link.valid_for = (expires_at - *created_at).max(1);
+	}
+	if kutt.banned {
+		link.status = LinkState::Disabled;
+		link.disabled_reason = Some(DISABLED_BANNED.to_owned());
+	}
+
+
+	Ok(link)
+}
+
+/// Keywords the statements of a MySQL dump start with.
+const SQL_KEYWORDS: [&str; 8] = ["ALTER", "CREATE", "DROP", "INSERT", "LOCK", "SET", "START", "USE"];
+
+/// Whether a YOURLS export is a SQL dump rather than CSV, judged by its first statement behind any comments,
+/// like the `/*!40101 SET ... */;` of `mysqldump`.
+fn is_sql_dump(input: &[u8]) -> bool {
+	let mut start = 0;
+	loop {
+		while input.get(start).is_some_and(|byte| byte.is_ascii_whitespace() || *byte == b';') {
+			start += 1;
+		}
+		match comment_end(input, start) {
+			Some(end) => start = end,
+			None => break,
+		}
+	}
+
+	let statement = &input[start..];
+	let length = statement.iter().position(|byte| !byte.is_ascii_alphabetic()).unwrap_or(statement.len());
+	// A keyword has to be followed by whitespace, `set,https://example.com` is a CSV row.
+	statement.get(length).is_some_and(u8::is_ascii_whitespace)
+		&& SQL_KEYWORDS.iter().any(|keyword| statement[..length].eq_ignore_ascii_case(keyword.as_bytes()))
+}
+
+/// Where the comment starting at `start` ends, if one starts there.
+/// `--` and `#` comments end with the line, `/* */` comments with the `*/`.
+fn comment_end(input: &[u8], start: usize) -> Option<usize> {
+	let rest = &input[start..];
+	if rest.starts_with(b"--") || rest.starts_with(b"#") {
+		Some(rest.iter().position(|byte| *byte == b'\n').map_or(input.len(), |end| start + end + 1))
+	} else if rest.starts_with(b"/*") {
+		Some(rest.windows(2).skip(2).position(|window| window == b"*/").map_or(input.len(), |end| start + end + 4))
+	} else {
+		None
+	}
+}
+
+/// Where the string or quoted name starting at `start` ends, behind its closing quote.
+/// A doubled quote ends the string and starts the next one, which skips both all the same.
+fn quoted_end(input: &[u8], start: usize) -> usize {
+	let quote = input[start];
+	let mut index = start + 1;
+	while let Some(byte) = input.get(index) {
+		match *byte {
+			b'\\' if quote != b'`' => index += 2,
+			byte if byte == quote => return index + 1,
+			_ => index += 1,
+		}
+	}
+
+
+	input.len()
+}
+
+/// Reads the rows inserted into the YOURLS URL table, whatever its prefix, from a MySQL dump.
+/// Inserts into other tables are skipped. Statements that can't be read are reported and skipped as well,
+/// so the links don't depend on every row of the other tables being understood.
+fn yourls_sql(input: &str) -> Vec<(u64, Result<ExportedLink, String>)> {
+	let mut reader = SqlReader { input, position: 0, line: 1 };
+	let mut links = Vec::new();
+	while reader.skip_to_insert() {
+		let (start, start_line) = (reader.position, reader.line);
+		let insert = match reader.insert_statement() {
+			Ok(insert) => insert,
+			Err(why) => {
+				links.push((reader.line, Err(why)));
+				// The statement is skipped from its start, the error might be in the middle of a string.
+				reader.position = start;
+				reader.line = start_line;
+				reader.skip_statement();
+				continue;
+			}
+		};
+		if insert.table != "url" && !insert.table.ends_with("_url") {
+			continue;
+		}
+
+		let columns = insert.columns.unwrap_or_else(|| YOURLS_COLUMNS.iter().map(|column| (*column).to_owned()).collect());
+		for (line, values) in insert.rows {
+			if values.len() != columns.len() {
+				links.push((line, Err(format!("the row has {} values for {} columns", values.len(), columns.len()))));
+				continue;
+			}
+
+			let row = columns.iter()
+				.cloned()
+				.zip(values)
+				.filter_map(|(column, value)| Some((column, value?)))
+				.collect::<Row>();
+			links.push((line, yourls_link(&row)));
+		}
+	}
+
+
+	links
+}
+
+/// An `INSERT` statement of a SQL dump.
+struct Insert {
+	table: String,
+	/// The columns the values are for, if the statement lists them.
+	columns: Option<Vec<String>>,
+	/// The values of every row with the line it starts on. `NULL` is `None`.
+	rows: Vec<(u64, Vec<Option<String>>)>,
+}
+
+/// Just enough of a SQL reader for the `INSERT` statements of MySQL dumps.
+struct SqlReader<'a> {
+	input: &'a str,
+	position: usize,
+	line: u64,
+}
+
+impl SqlReader<'_> {
+	fn rest(&self) -> &str {
+		&self.input[self.position..]
+	}
+
+	fn advance(&mut self, bytes: usize) {
+		let end = self.position + bytes;
+		self.line += self.input[self.position..end].matches('\n').count() as u64;
+		self.position = end;
+	}
+
+	fn skip_whitespace(&mut self) {
+		let whitespace = self.rest().len() - self.rest().trim_start().len();
+		self.advance(whitespace);
+	}
+
+	/// Consumes the keyword or punctuation if it comes next, ignoring case and leading whitespace.
+	fn eat(&mut self, expected: &str) -> bool {
+		self.skip_whitespace();
+		let matches = self.rest().get(..expected.len()).is_some_and(|next| next.eq_ignore_ascii_case(expected));
+		if matches {
+			self.advance(expected.len());
+		}
+
+
+		matches
+	}
+
+	fn expect(&mut self, expected: &str) -> Result<(), String> {
+		if self.eat(expected) {
+			Ok(())
+		} else {
+			Err(format!("expected `{expected}` on line {}", self.line))
+		}
+	}
+
+	/// Moves behind the next `INSERT INTO` that isn't part of a string, a quoted name or a comment.
+	/// Returns whether there is one.
+	fn skip_to_insert(&mut self) -> bool {
+		let bytes = self.input.as_bytes();
+		let mut index = self.position;
+		while index < bytes.len() {
+			if let Some(end) = comment_end(bytes, index) {
+				index = end;
+				continue;
+			}
+
+			match bytes[index] {
+				b'\'' | b'"' | b'`' => index = quoted_end(bytes, index),
+				byte if byte.is_ascii_alphabetic() => {
+					let length = bytes[index..].iter()
+						.position(|byte| !(byte.is_ascii_alphanumeric() || *byte == b'_'))
+						.unwrap_or(bytes.len() - index);
+					index += length;
+					if bytes[index - length..index].eq_ignore_ascii_case(b"INSERT") {
+						self.advance(index - self.position);
+						self.eat("IGNORE");
+						if self.eat("INTO") {
+							return true;
+						}
+						index = self.position;
+					}
+				}
+				_ => index += 1,
+			}
+		}
+		self.advance(bytes.len() - self.position);
+
+
+		false
+	}
+
+	/// Moves behind the `;` that ends the current statement, which isn't part of a string, a quoted name or a comment.
+	fn skip_statement(&mut self) {
+		let bytes = self.input.as_bytes();
+		let mut index = self.position;
+		while index < bytes.len() {
+			if let Some(end) = comment_end(bytes, index) {
+				index = end;
+				continue;
+			}
+
+			match bytes[index] {
+				b'\'' | b'"' | b'`' => index = quoted_end(bytes, index),
+				b';' => {
+					index += 1;
+					break;
+				}
+				_ => index += 1,
+			}
+		}
+		self.advance(index - self.position);
+	}
+
+	/// Reads the table, the columns if they are listed and the rows of an `INSERT` statement.
+	fn insert_statement(&mut self) -> Result<Insert, String> {
+		let table = self.identifier()?.to_lowercase();
+		let table = table.rsplit('.').next().unwrap_or_default().to_owned();
+		let columns = if self.eat("(") {
+			let mut columns = vec![self.identifier()?.to_lowercase()];
+			while self.eat(",") {
+				columns.push(self.identifier()?.to_lowercase());
+			}
+			self.expect(")")?;
+			Some(columns)
+		} else {
+			None
+		};
+		self.expect("VALUES")?;
+
+		let mut rows = Vec::new();
+		loop {
+			self.expect("(")?;
+			let line = self.line;
+			let mut values = vec![self.value()?];
+			while self.eat(",") {
+				values.push(self.value()?);
+			}
+			self.expect(")")?;
+			rows.push((line, values));
+
+			if !self.eat(",") {
+				break;
+			}
+		}
+		if !self.eat(";") {
+			self.skip_whitespace();
+		}
+
+
+		Ok(Insert { table, columns, rows })
+	}
+
+	/// A table or column name, optionally quoted with backticks.
+	fn identifier(&mut self) -> Result<String, String> {
+		self.skip_whitespace();
+		let rest = self.rest();
+		let length = rest.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '`' | '$')))
+			.unwrap_or(rest.len());
+		if length == 0 {
+			return Err(format!("expected a name on line {}", self.line));
+		}
+		let identifier = rest[..length].replace('`', "");
+		self.advance(length);
+
+
+		Ok(identifier)
+	}
+
+	/// A string, a number or `NULL`.
+	fn value(&mut self) -> Result<Option<String>, String> {
+		self.skip_whitespace();
+		if self.rest().starts_with('\'') {
+			return self.string().map(Some);
+		}
+		if self.eat("NULL") {
+			return Ok(None);
+		}
+
+		let rest = self.rest();
+		let length = rest.find([',', ')']).unwrap_or(rest.len());
+		let value = rest[..length].trim().to_owned();
+		if value.is_empty() {
+			return Err(format!("expected a value on line {}", self.line));
+		}
+		self.advance(length);
+
+
+		Ok(Some(value))
+	}
+
+	/// A single quoted string with the escapes of MySQL.
+	fn string(&mut self) -> Result<String, String> {
+		let start = self.line;
+		let mut value = String::new();
+		let mut chars = self.rest().char_indices().skip(1);
+		while let Some((index, c)) = chars.next() {
+			match c {
+				'\\' => match chars.next() {
+					Some((_, 'n')) => value.push('\n'),
+					Some((_, 'r')) => value.push('\r'),
+					Some((_, 't')) => value.push('\t'),
+					Some((_, '0')) => value.push('\0'),
+					Some((_, escaped)) => value.push(escaped),
+					None => break,
+				},
+				'\'' if self.rest()[index + 1..].starts_with('\'') => {
+					chars.next();
+					value.push('\'');
+				}
+				'\'' => {
+					self.advance(index + 1);
+					return Ok(value);
+				}
+				c => value.push(c),
+			}
+		}
+
+
+		Err(format!("unterminated string starting on line {start}"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type Parsed = (Vec<(u64, ExportedLink)>, Vec<(u64, String)>);
+
+	/// Parses the export and splits the links from the errors, with their lines.
+	fn parse_all(source: Source, input: &str) -> Parsed {
+		let mut links = Vec::new();
+		let mut errors = Vec::new();
+		for (line, link) in parse(source, input.as_bytes(), "example.com") {
+			match link {
+				Ok(link) => links.push((line, link)),
+				Err(why) => errors.push((line, why)),
+			}
+		}
+
+
+		(links, errors)
+	}
+
+	const MYSQL_DUMP: &str = r#"-- MySQL dump 10.13  Distrib 8.0.36, for Linux (x86_64)
+--
+-- Host: localhost    Database: yourls
+-- ------------------------------------------------------
+
+/*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;
+/*!40101 SET NAMES utf8mb4 */;
+
+--
+-- Table structure for table `yourls_log`
+--
+
+DROP TABLE IF EXISTS `yourls_log`;
+CREATE TABLE `yourls_log` (
+  `click_id` int NOT NULL AUTO_INCREMENT,
+  `shorturl` varchar(100) NOT NULL,
+  PRIMARY KEY (`click_id`)
+);
+
+LOCK TABLES `yourls_log` WRITE;
+/*!40000 ALTER TABLE `yourls_log` DISABLE KEYS */;
+INSERT INTO `yourls_log` VALUES (1,'2024-01-01 10:00:00','abc','https://ref.example/?q=INSERT INTO yourls_url VALUES (1);');
+/*!40000 ALTER TABLE `yourls_log` ENABLE KEYS */;
+UNLOCK TABLES;
+
+# insert into `yourls_url` values ('comment', 'https://comment.example');
+LOCK TABLES `yourls_url` WRITE;
+INSERT INTO `yourls_url` VALUES ('abc','https://example.com/a','It\'s \"quoted\"','2024-01-01 10:00:00','127.0.0.1',5),
+('def','https://example.com/b?x=1,2','Doubled '' quote','2024-01-02 10:00:00','127.0.0.1',0),
+('ghi','https://example.com/c',NULL,'2024-01-03 10:00:00','127.0.0.1',12);
+UNLOCK TABLES;
+"#;
+
+	#[test]
+	fn reads_mysql_dump() {
+		let (links, errors) = parse_all(Source::Yourls, MYSQL_DUMP);
+		assert!(errors.is_empty(), "{errors:?}");
+
+		let ids = links.iter().map(|(_, link)| link.id.as_str()).collect::<Vec<_>>();
+		assert_eq!(ids, ["abc", "def", "ghi"]);
+		assert_eq!(links[0].1.redirect_to, "https://example.com/a");
+		assert_eq!(links[0].1.invocations, 5);
+		assert_eq!(links[1].1.redirect_to, "https://example.com/b?x=1,2");
+		assert_eq!(links[2].1.invocations, 12);
+		assert!(links.iter().all(|(_, link)| link.domain == "example.com"));
+		// Every row has the line it starts on.
+		assert_eq!(links.iter().map(|(line, _)| *line).collect::<Vec<_>>(), [28, 29, 30]);
+	}
+
+	#[test]
+	fn reads_prefixed_table_with_columns() {
+		let dump = "INSERT INTO yourls.`links_url` (`url`, `keyword`) VALUES ('https://example.com/', 'first');\n\
+			INSERT INTO `url` VALUES ('second', 'https://example.com/2', '', '2024-01-01 00:00:00', '::1', 3);";
+		let (links, errors) = parse_all(Source::Yourls, dump);
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(links[0].1.id, "first");
+		assert_eq!(links[0].1.redirect_to, "https://example.com/");
+		assert_eq!(links[1].1.id, "second");
+		assert_eq!(links[1].1.invocations, 3);
+	}
+
+	#[test]
+	fn skips_unreadable_statements() {
+		let dump = "INSERT INTO `yourls_options` VALUES oops ('a; INSERT INTO yourls_url VALUES (\\'evil\\')');\n\
+			INSERT INTO `yourls_url` VALUES ('abc','https://example.com/a','','2024-01-01 00:00:00','::1',0),('short');";
+		let (links, errors) = parse_all(Source::Yourls, dump);
+		assert_eq!(links.len(), 1);
+		assert_eq!(links[0].1.id, "abc");
+		assert_eq!(errors.len(), 2);
+		assert_eq!(errors[1].1, "the row has 1 values for 6 columns");
+	}
+
+	#[test]
+	fn tells_sql_from_csv() {
+		assert!(is_sql_dump(MYSQL_DUMP.as_bytes()));
+		assert!(is_sql_dump(b"/*!40101 SET NAMES utf8 */;\nINSERT INTO url VALUES ('a', 'b');"));
+		assert!(is_sql_dump(b"insert into url values ('a', 'b');"));
+		assert!(!is_sql_dump(b"keyword,url,title\nabc,https://example.com/,INSERT INTO url"));
+		assert!(!is_sql_dump(b"set,https://example.com/set,Title"));
+		assert!(!is_sql_dump(b""));
+
+		let (links, errors) = parse_all(Source::Yourls, "keyword,url,title,timestamp,ip,clicks\nset,https://example.com/,Set,2024-01-01 00:00:00,::1,2\n");
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(links[0].1.id, "set");
+		assert_eq!(links[0].1.invocations, 2);
+	}
+
+	#[test]
+	fn parses_timestamps() {
+		assert_eq!(parse_timestamp("1700000000"), Some(1_700_000_000_000));
+		assert_eq!(parse_timestamp("1700000000123"), Some(1_700_000_000_123));
+		assert_eq!(parse_timestamp("2024-01-01T00:00:00Z"), Some(1_704_067_200_000));
+		assert_eq!(parse_timestamp("2024-01-01T01:00:00.500+01:00"), Some(1_704_067_200_500));
+		assert_eq!(parse_timestamp("2024-01-01 00:00:00 +0000"), Some(1_704_067_200_000));
+		let local = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
+			.and_local_timezone(Local).earliest().unwrap().timestamp_millis();
+		assert_eq!(parse_timestamp("2024-01-01 00:00:00"), Some(local));
+		assert_eq!(parse_timestamp("2024-01-01"), Some(local));
+		assert_eq!(parse_timestamp("yesterday"), None);
+	}
+
+	#[test]
+	fn reads_bitly_csv() {
+		let csv = "Bitlink,Custom bitlink,Long URL,Title,Created at,Total clicks\n\
+			bit.ly/3abcDEF,,https://example.com/a,A,2024-01-01T00:00:00Z,4\n\
+			https://bit.ly/3xyz,\"bit.ly/custom, bit.ly/other\",https://example.com/b,B,,\n";
+		let (links, errors) = parse_all(Source::Bitly, csv);
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(links[0].1.id, "3abcDEF");
+		assert_eq!(links[0].1.invocations, 4);
+		assert_eq!(links[0].1.created_at, Some(1_704_067_200_000));
+		assert_eq!(links[1].1.id, "custom");
+		assert_eq!(links[1].1.created_at, None);
+	}
+
+	#[test]
+	fn reads_kutt_json() {
+		let response = r#"{"limit": 10, "skip": 0, "total": 3, "data": [
+			{"address": "abc", "target": "https://example.com/a", "visit_count": 7, "created_at": "2024-01-01T00:00:00Z",
+				"expire_in": "2024-01-02T00:00:00Z", "password": false, "banned": false},
+			{"address": "secret", "target": "https://example.com/s", "password": true},
+			{"address": "bad", "target": "https://example.com/b", "banned": true}
+		]}"#;
+		let (links, errors) = parse_all(Source::Kutt, response);
+		assert_eq!(links.len(), 2);
+		assert_eq!(links[0].1.invocations, 7);
+		assert_eq!(links[0].1.valid_for, 24 * 60 * 60 * 1000);
+		assert_eq!(links[1].1.status, LinkState::Disabled);
+		assert_eq!(links[1].1.disabled_reason.as_deref(), Some(DISABLED_BANNED));
+		assert_eq!(errors, [(2, "the link secret is password protected, which shorty doesn't support".to_owned())]);
+
+		// A database dump has the password hash instead, and the array can be given without the wrapper.
+		let (links, errors) = parse_all(Source::Kutt, r#"[{"address": "x", "target": "https://example.com/", "password": "$2a$12$hash"}]"#);
+		assert!(links.is_empty());
+		assert_eq!(errors.len(), 1);
+
+		let (_, errors) = parse_all(Source::Kutt, r#"{"links": []}"#);
+		assert_eq!(errors.len(), 1);
+	}
+}
@@ -1,54 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use actix_web::web::Bytes;
 use chrono::Local;
-use serde::Deserialize;
-use sqlx::{Pool, Sqlite};
-use tracing::debug;
-use utoipa::ToSchema;
+use futures_util::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, FromRow, Pool, QueryBuilder, Sqlite, SqliteConnection};
+use tracing::{debug, info};
+use url::form_urlencoded;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{CONFIG, ensure_http_prefix};
+use crate::CONFIG;
+use crate::config::Config;
+use crate::config_validation::{ConfigProblem, ConfigProblems};
+use crate::alias::Alias;
+use crate::domain::Domain;
 use crate::error::ShortyError;
-use crate::util::{get_random_id, replace_illegal_url_chars, time_now};
+use crate::redirect::RedirectType;
+use crate::rules::{normalize_rules, Rule};
+use crate::variant::{check_weights, pick, Variant, VariantConfig};
+use crate::template::normalize_template;
+use crate::transfer::{self, ConflictPolicy, EXPORT_PAGE_SIZE, ExportedLink, Format, ImportOutcome, ImportResult};
+use crate::report::{ban_host, is_banned_host, Report};
+use crate::util::{get_random_id, normalization_option, normalize_id, normalize_target_url, replace_illegal_url_chars, time_now};
 
 /// This struct holds configuration options for a custom link.
-/// Optional fields are: `custom_id`, `max_uses`, and `valid_for`.
-/// `valid_for` and `max_uses` default to 0, which means essentially infinite.
-#[derive(Debug, Clone, Deserialize, ToSchema)]
+/// Optional fields are: `custom_id`, `max_uses`, `valid_for`, `redirect_type`, `prefix`, `template`, `append_query`, `rules`,
+/// `variants`, `weight`, `sticky`, `not_before`, `not_before_redirect_to` and `domain`.
+/// `valid_for`, `max_uses` and `append_query` default to the defaults of the domain, 0 means essentially infinite.
+/// `redirect_type` defaults to the `default_redirect_type` of the server, `prefix` and `template` to false.
+/// `domain` defaults to the domain the request was sent to.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
 #[schema(example = json!({"link": "https://google.com", "custom_id": "search", "max_uses": 0, "valid_for": 0}))]
 pub struct LinkConfig {
 	/// The link that should be shortened.
 	pub link: String,
 	/// Custom ID for the link (like when you want a word instead of random jumble of chars).
 	#[serde(alias = "id")]
-	custom_id: Option<String>,
+	pub custom_id: Option<String>,
 	/// How often the link may be used.
-	#[serde(default = "default_max_uses")]
-	max_uses: i64,
-	/// How long the link is valid for in milliseconds.
-	#[serde(default = "default_valid_for")]
-	valid_for: i64,
+	pub max_uses: Option<i64>,
+	/// How long the link is valid for in milliseconds, counted from `not_before` if it has one.
+	pub valid_for: Option<i64>,
+	/// The status code the link redirects with, 301, 302, 307 or 308.
+	pub redirect_type: Option<i64>,
+	/// Whether the link also matches longer paths, like `/{id}/some/page`.
+	/// The rest of the path and the query string are appended to the link then.
+	pub prefix: Option<bool>,
+	/// Whether the link is a template like `https://github.com/{1}/{2}?tab={tab}`. Like prefix links it
+	/// matches longer paths, `{1}` is filled in with the first segment of the path after the ID and
+	/// `{tab}` with the query parameter `tab`.
+	pub template: Option<bool>,
+	/// Query parameters added to the target on every redirect, unless it has them already,
+	/// e.g. `{"utm_source": "newsletter"}`. An empty object adds none, even if the domain has defaults.
+	pub append_query: Option<BTreeMap<String, String>>,
+	/// Rules sending some visitors somewhere else, e.g. iOS users to the App Store.
+	/// The first rule a visitor matches wins, visitors matching none are sent to `link`.
+	pub rules: Option<Vec<Rule>>,
+	/// More targets to split the visitors between, e.g. for A/B tests. Visitors matching a rule always follow the rule.
+	pub variants: Option<Vec<VariantConfig>>,
+	/// The weight of `link` itself if there are `variants`, 1 by default. 0 sends all visitors to the variants.
+	pub weight: Option<u32>,
+	/// Whether visitors keep getting the same variant, remembered with a cookie.
+	pub sticky: Option<bool>,
+	/// When the link goes live, in milliseconds. Before that it shows a page saying it isn't available yet.
+	pub not_before: Option<i64>,
+	/// Where the link redirects to before `not_before` instead of showing that page.
+	pub not_before_redirect_to: Option<String>,
+	/// Whether an existing link to the same target is returned instead of a new one, defaults to `deduplicate_links`
+	/// of the server. Only links without a custom ID, limits or other settings are deduplicated,
+	/// which includes limits from the defaults of the domain.
+	pub deduplicate: Option<bool>,
+	/// The host of the domain the link should be shortened on.
+	pub domain: Option<String>,
 }
 
-/// This function exists only because serde's default can't take values or a value from a struct.
-fn default_max_uses() -> i64 {
-	CONFIG.default_max_uses
+/// The outcome of creating one link of a batch. Either `url` or `error` is set.
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(example = json!({"status": 409, "error": "Link with provided ID already exists"}))]
+pub struct BatchResult {
+	/// The status code creating the link on its own would have been answered with.
+	pub status: u16,
+	/// The shortened URL, if the link was created.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	/// Why the link couldn't be created.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
 }
 
-/// This function exists only because serde's default can't take values or a value from a struct.
-fn default_valid_for() -> i64 {
-	CONFIG.default_valid_for
+impl From<Result<Link, ShortyError>> for BatchResult {
+	fn from(result: Result<Link, ShortyError>) -> Self {
+		match result {
+			Ok(link) => Self { status: StatusCode::OK.as_u16(), url: Some(link.formatted()), error: None },
+			Err(why) => Self { status: why.status_code().as_u16(), url: None, error: Some(why.to_string()) },
+		}
+	}
+}
+
+impl LinkConfig {
+	/// Creates a link config for the link. Everything else uses the defaults, other fields can be set
+	/// with struct update syntax: `LinkConfig { max_uses: Some(1), ..LinkConfig::new(link) }`.
+	#[must_use]
+	pub fn new(link: String) -> Self {
+		Self {
+			link,
+			..Self::default()
+		}
+	}
+}
+
+/// The `disabled_reason` of links that got disabled because they were reported too often.
+pub const DISABLED_REPORTED: &str = "reported";
+/// The `disabled_reason` of links that were banned by an admin.
+pub const DISABLED_BANNED: &str = "banned";
+/// The `disabled_reason` of links that were disabled by an admin.
+pub const DISABLED_BY_ADMIN: &str = "disabled";
+
+/// How many path segments a requested path may have. Every segment can be the end of the ID of a prefix link,
+/// so each one costs a lookup, and paths with more segments are taken as unknown without any.
+const MAX_PATH_SEGMENTS: usize = 32;
+
+/// The state a link is in, as it is stored in the `status` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkState {
+	Active,
+	/// The link was disabled, see [`Link::disabled_reason`] for why.
+	Disabled,
+	/// The link ran out of time or uses.
+	Expired,
+	/// The link was deleted. It is kept as a record until its ID is used again.
+	Deleted,
+}
+
+impl LinkState {
+	#[must_use]
+	pub fn as_str(self) -> &'static str {
+		match self {
+			LinkState::Active => "active",
+			LinkState::Disabled => "disabled",
+			LinkState::Expired => "expired",
+			LinkState::Deleted => "deleted",
+		}
+	}
+}
+
+/// The outcome of looking up a link to redirect to it.
+#[derive(Debug)]
+pub enum Lookup {
+	/// The link can be used. The use isn't counted yet, see [`LinkStore::count_use`].
+	/// For prefix and template links the rest of the requested path after the ID is included, starting with a `/`.
+	Active(Link, String),
+	/// The link exists, but isn't live yet. This use wasn't counted.
+	Pending(Link),
+	/// The link exists, but is disabled or expired.
+	Gone(Link),
+	/// There is no link with the ID, or it was deleted.
+	NotFound,
+}
+
+/// Filters for listing links. All filters are optional and combined.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LinkFilter {
+	/// Only links on the domain with this host.
+	pub domain: Option<String>,
+	/// Only links redirecting to this host.
+	pub target: Option<String>,
+	/// Only links created at or after this point in time, in milliseconds.
+	pub created_after: Option<i64>,
+	/// Only links created before this point in time, in milliseconds.
+	pub created_before: Option<i64>,
+	/// Only links in this state. Deleted links are only listed if they are asked for.
+	pub state: Option<LinkState>,
+}
+
+/// Numbers describing the whole instance.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Totals {
+	pub links: i64,
+	pub active: i64,
+	pub expired: i64,
+	pub disabled: i64,
+	pub deleted: i64,
+	/// How often all links were used in total.
+	pub invocations: i64,
+	pub open_reports: i64,
+	pub banned_domains: i64,
 }
 
 /// Struct representing a (shortened) Link.
 /// All timestamps are in milliseconds.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, FromRow)]
 pub struct Link {
+	/// The name of the [`Domain`] the link belongs to.
+	pub domain: String,
 	pub id: String,
+	/// The ID as it is compared in lookups, see [`normalize_id`].
+	pub normalized_id: String,
 	pub redirect_to: String,
-	max_uses: i64,
-	invocations: i64,
-	created_at: i64,
-	valid_for: i64,
+	pub max_uses: i64,
+	pub invocations: i64,
+	pub created_at: i64,
+	pub valid_for: i64,
+	/// The status code the link redirects with, see [`RedirectType`].
+	pub redirect_type: i64,
+	/// Whether the link also matches longer paths, see [`LinkConfig::prefix`].
+	pub prefix: bool,
+	/// Whether `redirect_to` is a template, see [`LinkConfig::template`].
+	pub template: bool,
+	/// The form-urlencoded query parameters added to the target, see [`LinkConfig::append_query`].
+	/// `None` uses the defaults of the domain at the time of the redirect.
+	pub append_query: Option<String>,
+	/// The [`Rule`]s of the link as a JSON array, see [`Link::rules`].
+	pub rules: Option<String>,
+	/// How many [`Variant`]s the link has, including its own target. 0 if it has none.
+	pub variant_count: i64,
+	/// Whether visitors keep getting the same variant.
+	pub sticky: bool,
+	/// When the link goes live. It is live from its creation on if this is `None`.
+	pub not_before: Option<i64>,
+	/// Where the link redirects to before it goes live, instead of showing a page saying it isn't available yet.
+	pub not_before_redirect_to: Option<String>,
+	/// Why the link was disabled, [`DISABLED_REPORTED`], [`DISABLED_BANNED`] or [`DISABLED_BY_ADMIN`].
+	/// Disabled links don't redirect anymore.
+	pub disabled_reason: Option<String>,
+	/// The [`LinkState`] the link was last put in.
+	/// Links that ran out of time or uses might still be `active` until they are looked up or cleaned.
+	pub status: String,
+	/// When the status last changed. For expired links this is when they expired.
+	pub status_changed_at: Option<i64>,
+	/// Whether the link was created without a custom ID, limits or other settings,
+	/// so it can be returned when its target is shortened again.
+	pub shareable: bool,
 }
 
 impl Display for Link {
@@ -66,20 +253,19 @@ impl Link {
 	/// Errors if the underlying [`Link::new_with_config`] errors.
 	pub async fn new(
 		link: String,
-		pool: &Pool<Sqlite>,
+		domain: &Domain,
+		conn: &mut SqliteConnection,
 	) -> Result<Self, ShortyError> {
-		let link_config = LinkConfig {
-			link,
-			custom_id: None,
-			max_uses: CONFIG.default_max_uses,
-			valid_for: CONFIG.default_valid_for,
-		};
+		let link_config = LinkConfig::new(link);
 
 
-		Link::new_with_config(link_config, pool).await
+		Link::new_with_config(link_config, domain, conn).await
 	}
 
 	/// Creates a new link according to the config provided.
+	/// The link is created on `domain`, unless the config asks for a different one.
+	/// If the link should be deduplicated, an existing shareable link to the same target that is still active
+	/// and unrestricted is returned instead if there is one.
 	///
 	/// # Errors
 	///
@@ -87,69 +273,174 @@ impl Link {
 	/// Also returns an error if there was a problem executing the SQL queries.
 	pub async fn new_with_config(
 		link_config: LinkConfig,
-		pool: &Pool<Sqlite>,
+		domain: &Domain,
+		conn: &mut SqliteConnection,
 	) -> Result<Self, ShortyError> {
-		let id = if let Some(id) = link_config.custom_id {
-			if id.len() > CONFIG.max_custom_id_length {
-				return Err(ShortyError::CustomIDExceedsMaxLength);
-			}
+		let domain = match link_config.domain {
+			Some(ref requested) => Domain::requested(requested)?,
+			None => domain.clone(),
+		};
 
-			replace_illegal_url_chars(&id)
+		let deduplicate = link_config.deduplicate.unwrap_or(CONFIG.load().deduplicate_links);
+		let has_custom_id = link_config.custom_id.is_some();
+		let id = if let Some(id) = link_config.custom_id {
+			custom_id(id.as_str())?
 		} else {
-			get_random_id(pool).await?
+			get_random_id(domain.name.as_str(), conn).await?
 		};
 		let redirect_to = link_config.link;
-		let max_uses = link_config.max_uses;
+		let max_uses = link_config.max_uses.unwrap_or(domain.default_max_uses);
 		let invocations = 0;
 		let created_at = time_now();
-		let valid_for = link_config.valid_for;
+		let valid_for = link_config.valid_for.unwrap_or(domain.default_valid_for);
+		let prefix = link_config.prefix.unwrap_or(false);
+		let template = link_config.template.unwrap_or(false);
+		let rules = match link_config.rules {
+			Some(rules) if !rules.is_empty() => Some(normalize_rules(rules, template, &CONFIG.load())?),
+			_ => None,
+		};
+		for rule in rules.iter().flatten() {
+			ensure_host_not_banned(rule.redirect_to.as_str(), conn).await?;
+		}
+		let rules = rules.map(|rules| serde_json::to_string(&rules)).transpose()
+			.map_err(|why| ShortyError::InvalidRule(why.to_string()))?;
+		let variants = link_config.variants.unwrap_or_default();
+		let weight = link_config.weight.unwrap_or(1);
+		check_weights(weight, variants.as_slice())?;
+		let mut variants = variants;
+		for variant in &mut variants {
+			variant.redirect_to = if template {
+				normalize_template(variant.redirect_to.as_str(), &CONFIG.load())?
+			} else {
+				normalize_target_url(variant.redirect_to.as_str(), &CONFIG.load())?
+			};
+			ensure_host_not_banned(variant.redirect_to.as_str(), conn).await?;
+		}
+		let variant_count = if variants.is_empty() { 0 } else { i64::try_from(variants.len() + 1).unwrap_or(i64::MAX) };
+		let sticky = !variants.is_empty() && link_config.sticky.unwrap_or(false);
+		// Links that are live right away don't need a start.
+		let not_before = link_config.not_before.filter(|not_before| *not_before > created_at);
+		let not_before_redirect_to = match link_config.not_before_redirect_to {
+			Some(ref target) if not_before.is_some() => {
+				let target = normalize_target_url(target.as_str(), &CONFIG.load())?;
+				ensure_host_not_banned(target.as_str(), conn).await?;
+				Some(target)
+			}
+			_ => None,
+		};
+		let append_query = link_config.append_query.map(|parameters| {
+			form_urlencoded::Serializer::new(String::new())
+				.extend_pairs(parameters.iter().filter(|(key, _)| !key.is_empty()))
+				.finish()
+		});
+		if prefix && template {
+			return Err(ShortyError::PrefixTemplate);
+		}
+		let redirect_type = match link_config.redirect_type {
+			Some(code) => RedirectType::try_from(code)?.into(),
+			None => CONFIG.load().default_redirect_type,
+		};
 
 		if redirect_to.is_empty() {
 			return Err(ShortyError::LinkEmpty);
 		}
 
-		if redirect_to.len() > CONFIG.max_link_length {
+		if redirect_to.len() > CONFIG.load().max_link_length {
 			return Err(ShortyError::LinkExceedsMaxLength);
 		}
 
-		let redirect_to = ensure_http_prefix(redirect_to);
+		let redirect_to = if template {
+			normalize_template(redirect_to.as_str(), &CONFIG.load())?
+		} else {
+			normalize_target_url(redirect_to.as_str(), &CONFIG.load())?
+		};
+		ensure_host_not_banned(redirect_to.as_str(), conn).await?;
 
-		// If a link with the same ID exists already, return a conflict error.
-		if let Some(link) = Link::from_id_no_invocation(id.as_str(), pool).await? {
-			if !link.is_expired() {
-				return Err(ShortyError::LinkConflict);
+		// Only unrestricted links are shared, a link with limits would run out for everyone who got it.
+		// That includes limits from the defaults of the domain. Links with a custom ID or settings aren't shared either.
+		let shareable = !has_custom_id && max_uses == 0 && valid_for == 0 && !prefix && !template && rules.is_none() && variants.is_empty()
+			&& not_before.is_none() && append_query.is_none();
+		if deduplicate && shareable {
+			if let Some(existing) = Link::shared_to(domain.name.as_str(), redirect_to.as_str(), redirect_type, conn).await? {
+				debug!("{} was shortened before as {}.", redirect_to, existing.id);
+				return Ok(existing);
 			}
 		}
 
+		let normalized_id = normalize_id(id.as_str(), &CONFIG.load());
+
+		let existing = Link::free_id(domain.name.as_str(), id.as_str(), normalized_id.as_str(), created_at, conn).await?;
+
 		let shortened = Self {
+			domain: domain.name,
 			id,
+			normalized_id,
 			redirect_to,
 			max_uses,
 			invocations,
 			created_at,
 			valid_for,
+			redirect_type,
+			prefix,
+			template,
+			append_query,
+			rules,
+			variant_count,
+			sticky,
+			not_before,
+			not_before_redirect_to,
+			disabled_reason: None,
+			status: LinkState::Active.as_str().to_owned(),
+			status_changed_at: None,
+			shareable,
 		};
 
 		if shortened.is_expired() {
 			return Err(ShortyError::ExpiredLinkProvided);
 		}
 
-		// We checked if the link exists already and is valid.
-		// If it exists it has to be stale and can be replaced.
+		// We checked if links with the same ID exist already and are valid.
+		// If they exist they have to be stale and are moved to the history.
+		for stale in existing {
+			Link::archive(stale.domain.as_str(), stale.id.as_str(), conn).await?;
+		}
+
 		sqlx::query!(
 			r#"
-				INSERT OR REPLACE INTO links
-				VALUES ($1, $2, $3, $4, $5, $6)
+				INSERT INTO links (domain, id, normalized_id, redirect_to, max_uses, invocations, created_at, valid_for, redirect_type, prefix, template,
+					append_query, rules, variant_count, sticky, not_before, not_before_redirect_to, shareable)
+				VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
 			"#,
+			shortened.domain,
 			shortened.id,
+			shortened.normalized_id,
 			shortened.redirect_to,
 			max_uses,
 			invocations,
 			created_at,
-			valid_for
+			valid_for,
+			redirect_type,
+			prefix,
+			template,
+			shortened.append_query,
+			shortened.rules,
+			variant_count,
+			sticky,
+			shortened.not_before,
+			shortened.not_before_redirect_to,
+			shareable,
 		)
-			.execute(pool)
-			.await?;
+			.execute(&mut *conn)
+			.await
+			.map_err(|error| match error {
+				// Another request took the ID between the check above and the insert.
+				sqlx::Error::Database(ref database_error) if database_error.is_unique_violation() => ShortyError::LinkConflict,
+				error => error.into(),
+			})?;
+
+		if !variants.is_empty() {
+			Variant::create_all(&shortened, weight, variants.as_slice(), conn).await?;
+		}
 
 
 		Ok(shortened)
@@ -160,8 +451,12 @@ impl Link {
 	/// expired time-wise.
 	#[must_use]
 	pub fn is_expired(&self) -> bool {
+		if self.status == LinkState::Expired.as_str() {
+			return true;
+		}
+
 		let time_expired = self.valid_for < 0 || (self.valid_for > 0
-			&& (Local::now().timestamp_millis() - self.created_at) > self.valid_for);
+			&& (Local::now().timestamp_millis() - self.starts_at()) > self.valid_for);
 
 		let uses_invalid = self.max_uses < 0
 			|| (self.max_uses > 0 && self.invocations >= self.max_uses);
@@ -171,20 +466,106 @@ impl Link {
 		time_expired || uses_invalid
 	}
 
-	/// Retrieves a link from the database, if it exists.
-	/// Calling this function also increments the invocations if the link exists in the database.
-	async fn from_id(id: &str, pool: &Pool<Sqlite>) -> Result<Option<Self>, ShortyError> {
+	/// When the link expired, if it did.
+	/// Links that ran out of time expired when their time was up. For links that ran out of uses
+	/// that is only known once their status was set, until then they are taken to expire right now.
+	#[must_use]
+	pub fn expired_at(&self) -> Option<i64> {
+		if !self.is_expired() {
+			return None;
+		}
+
+		if self.status == LinkState::Expired.as_str() {
+			if let Some(status_changed_at) = self.status_changed_at {
+				return Some(status_changed_at);
+			}
+		}
+
+		let now = time_now();
+
+
+		Some(self.expires_at().filter(|expires_at| *expires_at <= now).unwrap_or(now))
+	}
+
+	/// The rules of the link, in the order they are matched in.
+	#[must_use]
+	pub fn rules(&self) -> Vec<Rule> {
+		self.rules.as_deref()
+			.and_then(|rules| serde_json::from_str(rules).ok())
+			.unwrap_or_default()
+	}
+
+	/// The state of the link, see [`LinkState`].
+	#[must_use]
+	pub fn state(&self) -> LinkState {
+		match self.status.as_str() {
+			"deleted" => LinkState::Deleted,
+			"disabled" => LinkState::Disabled,
+			_ if self.is_expired() => LinkState::Expired,
+			_ => LinkState::Active,
+		}
+	}
+
+	/// Counts a use of the link.
+	async fn count_use(&self, pool: &Pool<Sqlite>) -> Result<(), ShortyError> {
+		sqlx::query!(
+			r#"
+			UPDATE links
+			SET invocations = invocations + 1
+			WHERE domain = $1 AND id = $2;
+			"#,
+			self.domain,
+			self.id,
+		)
+			.execute(pool)
+			.await?;
+
+
+		Ok(())
+	}
+
+	/// Stores that the link expired, if that isn't stored already.
+	async fn mark_expired(&self, pool: &Pool<Sqlite>) -> Result<(), ShortyError> {
+		if self.status != LinkState::Active.as_str() {
+			return Ok(());
+		}
+
+		let expired_at = self.expired_at().unwrap_or_else(time_now);
+		sqlx::query!(
+			r#"
+			UPDATE links
+			SET status = 'expired', status_changed_at = $1
+			WHERE domain = $2 AND id = $3 AND status = 'active';
+			"#,
+			expired_at,
+			self.domain,
+			self.id,
+		)
+			.execute(pool)
+			.await?;
+
+
+		Ok(())
+	}
+
+	/// Retrieves a link from the database, if it exists and wasn't deleted.
+	/// A link with exactly this ID is preferred, otherwise a link whose ID is the same after
+	/// normalization is returned.
+	/// This function **does not** increment the invocation counter of a link.
+	async fn from_id_no_invocation(domain: &str, id: &str, pool: &Pool<Sqlite>) -> Result<Option<Self>, ShortyError> {
+		let normalized_id = normalize_id(id, &CONFIG.load());
 		let link = sqlx::query_as!(
 			Self,
 			r#"
 			SELECT * FROM links
-			WHERE id = $1;
-			UPDATE links
-			SET invocations = invocations + 1
-			WHERE id = $2;
+			WHERE domain = $1 AND (id = $2 OR normalized_id = $3) AND status != 'deleted'
+			ORDER BY id = $4 DESC, created_at DESC
+			LIMIT 1;
 			"#,
+			domain,
+			id,
+			normalized_id,
 			id,
-			id
 		)
 			.fetch_optional(pool)
 			.await?;
@@ -193,47 +574,457 @@ impl Link {
 		Ok(link)
 	}
 
-	/// Retrieves a link from the database, if it exists.
-	/// This function **does not** increment the invocation counter of a link.
-	async fn from_id_no_invocation(id: &str, pool: &Pool<Sqlite>) -> Result<Option<Self>, ShortyError> {
+	/// Retrieves the newest shareable link on the domain to the target that is still active and unrestricted,
+	/// if there is one. It has to redirect with the same status code.
+	async fn shared_to(domain: &str, redirect_to: &str, redirect_type: i64, conn: &mut SqliteConnection) -> Result<Option<Self>, ShortyError> {
 		let link = sqlx::query_as!(
 			Self,
 			r#"
 			SELECT * FROM links
-			WHERE id = $1;
+			WHERE domain = $1 AND redirect_to = $2 AND status = 'active' AND redirect_type = $3 AND shareable
+				AND max_uses = 0 AND valid_for = 0
+			ORDER BY created_at DESC
+			LIMIT 1;
 			"#,
-			id,
+			domain,
+			redirect_to,
+			redirect_type,
 		)
-			.fetch_optional(pool)
+			.fetch_optional(&mut *conn)
 			.await?;
 
 
 		Ok(link)
 	}
 
-	/// Checks if the link exists in the database.
+	/// Retrieves all links on the domain with this ID or this normalized ID, whatever their state.
+	async fn same_ids(domain: &str, id: &str, normalized_id: &str, conn: &mut SqliteConnection) -> Result<Vec<Self>, ShortyError> {
+		let links = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT * FROM links
+			WHERE domain = $1 AND (id = $2 OR normalized_id = $3);
+			"#,
+			domain,
+			id,
+			normalized_id,
+		)
+			.fetch_all(&mut *conn)
+			.await?;
+
+
+		Ok(links)
+	}
+
+	/// Checks whether the ID can be used for a new link or alias, and returns the stale links that have to be moved
+	/// to the history before it is.
+	///
+	/// If a link or alias with the same ID, or one that is the same after normalization, exists already,
+	/// this returns a conflict error. Expired IDs are only free again after the grace period,
+	/// so a link doesn't suddenly lead somewhere else for people who still have it.
+	async fn free_id(domain: &str, id: &str, normalized_id: &str, now: i64, conn: &mut SqliteConnection) -> Result<Vec<Self>, ShortyError> {
+		if let Some(alias) = Alias::find(domain, id, normalized_id, conn).await? {
+			if alias.id == id {
+				return Err(ShortyError::LinkConflict);
+			}
+
+			return Err(ShortyError::NormalizedIdConflict(alias.id));
+		}
+
+		let existing = Link::same_ids(domain, id, normalized_id, conn).await?;
+		let grace_period = CONFIG.load().expired_id_grace_period;
+		for link in &existing {
+			match link.state() {
+				LinkState::Deleted => {}
+				LinkState::Expired => if link.expired_at().is_some_and(|expired_at| now < expired_at + grace_period) {
+					return Err(ShortyError::IdRecentlyExpired(link.id.clone()));
+				},
+				LinkState::Active | LinkState::Disabled if link.id == id => return Err(ShortyError::LinkConflict),
+				LinkState::Active | LinkState::Disabled => return Err(ShortyError::NormalizedIdConflict(link.id.clone())),
+			}
+		}
+
+
+		Ok(existing)
+	}
+
+	/// Checks if the link or an alias, or one whose ID is the same after normalization, exists in the database.
 	///
 	/// # Errors
 	///
 	/// Errors if there is some problem communicating with the database.
-	pub async fn link_exists(id: &str, pool: &Pool<Sqlite>) -> Result<bool, ShortyError> {
+	pub async fn link_exists(domain: &str, id: &str, conn: &mut SqliteConnection) -> Result<bool, ShortyError> {
+		let normalized_id = normalize_id(id, &CONFIG.load());
 		let link_row = sqlx::query!(r#"
-			SELECT id FROM links WHERE id = ?;
+			SELECT id FROM links WHERE domain = ? AND (id = ? OR normalized_id = ?);
 		"#,
-		id
+		domain,
+		id,
+		normalized_id
 		)
-			.fetch_optional(pool)
+			.fetch_optional(&mut *conn)
+			.await?;
+
+
+		if link_row.is_some() {
+			return Ok(true);
+		}
+
+
+		Ok(Alias::find(domain, id, normalized_id.as_str(), conn).await?.is_some())
+	}
+
+	/// Retrieves links ordered by creation time, oldest first.
+	/// This function **does not** increment the invocation counter of the links.
+	async fn list(limit: i64, offset: i64, pool: &Pool<Sqlite>) -> Result<Vec<Self>, ShortyError> {
+		let links = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT * FROM links
+			ORDER BY created_at, domain, id
+			LIMIT $1 OFFSET $2;
+			"#,
+			limit,
+			offset,
+		)
+			.fetch_all(pool)
+			.await?;
+
+
+		Ok(links)
+	}
+
+	/// Retrieves the links matching the filter ordered by creation time, newest first,
+	/// together with the number of links matching the filter in total.
+	/// This function **does not** increment the invocation counter of the links.
+	async fn search(
+		domain: Option<&str>,
+		filter: &LinkFilter,
+		limit: i64,
+		offset: i64,
+		pool: &Pool<Sqlite>,
+	) -> Result<(Vec<Self>, i64), ShortyError> {
+		let now = time_now();
+		let push_conditions = |query: &mut QueryBuilder<Sqlite>| {
+			query.push(" WHERE 1 = 1");
+			if let Some(domain) = domain {
+				query.push(" AND domain = ").push_bind(domain.to_owned());
+			}
+			if let Some(ref target) = filter.target {
+				// Targets are normalized URLs, so the host is always followed by a port, a slash or nothing.
+				let host = escape_like(target.trim().to_lowercase().as_str());
+				query.push(" AND (redirect_to LIKE ").push_bind(format!("%://{host}/%")).push(r" ESCAPE '\'")
+					.push(" OR redirect_to LIKE ").push_bind(format!("%://{host}:%")).push(r" ESCAPE '\'")
+					.push(" OR redirect_to LIKE ").push_bind(format!("%://{host}")).push(r" ESCAPE '\'")
+					.push(")");
+			}
+			if let Some(created_after) = filter.created_after {
+				query.push(" AND created_at >= ").push_bind(created_after);
+			}
+			if let Some(created_before) = filter.created_before {
+				query.push(" AND created_at < ").push_bind(created_before);
+			}
+			match filter.state {
+				Some(LinkState::Active) => {
+					query.push(" AND status = 'active' AND NOT ");
+					push_expired_condition(query, now);
+				}
+				Some(LinkState::Expired) => {
+					query.push(" AND (status = 'expired' OR (status = 'active' AND ");
+					push_expired_condition(query, now);
+					query.push("))");
+				}
+				Some(LinkState::Disabled) => { query.push(" AND status = 'disabled'"); }
+				Some(LinkState::Deleted) => { query.push(" AND status = 'deleted'"); }
+				None => { query.push(" AND status != 'deleted'"); }
+			}
+		};
+
+		let mut count = QueryBuilder::new("SELECT COUNT(*) FROM links");
+		push_conditions(&mut count);
+		let total = count.build_query_scalar::<i64>().fetch_one(pool).await?;
+
+		let mut select = QueryBuilder::new("SELECT * FROM links");
+		push_conditions(&mut select);
+		select.push(" ORDER BY created_at DESC, domain, id LIMIT ").push_bind(limit)
+			.push(" OFFSET ").push_bind(offset);
+		let links = select.build_query_as::<Self>().fetch_all(pool).await?;
+
+
+		Ok((links, total))
+	}
+
+	/// Counts the links, their uses, the open reports and the banned domains.
+	async fn totals(pool: &Pool<Sqlite>) -> Result<Totals, ShortyError> {
+		let now = time_now();
+		let mut query = QueryBuilder::new("SELECT COUNT(*) AS links, COALESCE(SUM(status = 'active' AND NOT ");
+		push_expired_condition(&mut query, now);
+		query.push("), 0) AS active, COALESCE(SUM(status = 'expired' OR (status = 'active' AND ");
+		push_expired_condition(&mut query, now);
+		query.push(r#")), 0) AS expired,
+			COALESCE(SUM(status = 'disabled'), 0) AS disabled,
+			COALESCE(SUM(status = 'deleted'), 0) AS deleted,
+			COALESCE(SUM(invocations), 0) AS invocations,
+			(SELECT COUNT(*) FROM reports WHERE resolved_at IS NULL) AS open_reports,
+			(SELECT COUNT(*) FROM banned_domains) AS banned_domains
+			FROM links"#);
+
+
+		Ok(query.build_query_as::<Totals>().fetch_one(pool).await?)
+	}
+
+	/// Marks the link with the provided ID as deleted and removes its aliases. It is kept until the ID is used again.
+	/// Returns whether there was a link to delete.
+	async fn delete(domain: &str, id: &str, pool: &Pool<Sqlite>) -> Result<bool, ShortyError> {
+		let now = time_now();
+		let result = sqlx::query!(
+			r#"
+			UPDATE links
+			SET status = 'deleted', status_changed_at = $1
+			WHERE domain = $2 AND id = $3 AND status != 'deleted';
+			"#,
+			now,
+			domain,
+			id,
+		)
+			.execute(pool)
+			.await?;
+		// Like the ID of the link, the IDs of its aliases can be used again right away.
+		if result.rows_affected() > 0 {
+			Alias::delete_for(domain, id, &mut *pool.acquire().await?).await?;
+		}
+
+
+		Ok(result.rows_affected() > 0)
+	}
+
+	/// Moves the link with the provided ID to the history, so the ID can be used for a new link.
+	async fn archive(domain: &str, id: &str, conn: &mut SqliteConnection) -> Result<(), ShortyError> {
+		let now = time_now();
+		let mut transaction = conn.begin().await?;
+		sqlx::query!(
+			r#"
+			INSERT INTO link_history (domain, id, normalized_id, redirect_to, max_uses, invocations, created_at, valid_for, redirect_type, prefix,
+				template, append_query, rules, variant_count, sticky, not_before, not_before_redirect_to, disabled_reason, status,
+				status_changed_at, replaced_at)
+			SELECT domain, id, normalized_id, redirect_to, max_uses, invocations, created_at, valid_for, redirect_type, prefix,
+				template, append_query, rules, variant_count, sticky, not_before, not_before_redirect_to, disabled_reason, status,
+				status_changed_at, $1
+			FROM links
+			WHERE domain = $2 AND id = $3;
+			"#,
+			now,
+			domain,
+			id,
+		)
+			.execute(&mut *transaction)
+			.await?;
+		sqlx::query!(
+			r#"
+			DELETE FROM links
+			WHERE domain = $1 AND id = $2;
+			"#,
+			domain,
+			id,
+		)
+			.execute(&mut *transaction)
+			.await?;
+		transaction.commit().await?;
+		// The reports and variants were about the link that was replaced, not the new one.
+		// Its aliases were deleted together with it.
+		Report::delete_for(domain, id, conn).await?;
+		Variant::delete_for(domain, id, conn).await?;
+
+
+		Ok(())
+	}
+
+	/// Frees the ID for an imported link that should replace whatever has it now.
+	/// Links with the ID, or one that is the same after normalization, are moved to the history and aliases are deleted.
+	/// Returns whether anything had the ID.
+	pub(crate) async fn release_id(domain: &str, id: &str, conn: &mut SqliteConnection) -> Result<bool, ShortyError> {
+		let id = custom_id(id)?;
+		let normalized_id = normalize_id(id.as_str(), &CONFIG.load());
+		let existing = Link::same_ids(domain, id.as_str(), normalized_id.as_str(), conn).await?;
+		for link in &existing {
+			Link::archive(link.domain.as_str(), link.id.as_str(), conn).await?;
+		}
+		let alias_released = Alias::release(domain, id.as_str(), normalized_id.as_str(), conn).await?;
+
+
+		Ok(!existing.is_empty() || alias_released)
+	}
+
+	/// Takes over what the config of an imported link doesn't cover: its uses, when it was created, when it starts and its status.
+	pub(crate) async fn restore(link: &Link, exported: &ExportedLink, conn: &mut SqliteConnection) -> Result<(), ShortyError> {
+		let status = exported.status.as_str();
+		sqlx::query!(
+			r#"
+			UPDATE links
+			SET invocations = $1, created_at = COALESCE($2, created_at), not_before = $3, not_before_redirect_to = $4,
+				status = $5, disabled_reason = $6, status_changed_at = $7
+			WHERE domain = $8 AND id = $9;
+			"#,
+			exported.invocations,
+			exported.created_at,
+			exported.not_before,
+			exported.not_before_redirect_to,
+			status,
+			exported.disabled_reason,
+			exported.status_changed_at,
+			link.domain,
+			link.id,
+		)
+			.execute(&mut *conn)
+			.await?;
+
+
+		Ok(())
+	}
+
+	/// Retrieves up to `limit` links ordered by domain and ID, starting after the link with `after_id` on `after_domain`.
+	/// This function **does not** increment the invocation counter of the links.
+	async fn export_page(after_domain: &str, after_id: &str, limit: i64, pool: &Pool<Sqlite>) -> Result<Vec<Self>, ShortyError> {
+		let links = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT * FROM links
+			WHERE (domain, id) > ($1, $2)
+			ORDER BY domain, id
+			LIMIT $3;
+			"#,
+			after_domain,
+			after_id,
+			limit,
+		)
+			.fetch_all(pool)
+			.await?;
+
+
+		Ok(links)
+	}
+
+	/// Retrieves the links the ID pointed to before, most recently replaced first.
+	async fn history(domain: &str, id: &str, pool: &Pool<Sqlite>) -> Result<Vec<Self>, ShortyError> {
+		let links = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT domain, id, normalized_id, redirect_to, max_uses, invocations, created_at, valid_for, redirect_type, prefix,
+				template, append_query, rules, variant_count, sticky, not_before, not_before_redirect_to, disabled_reason, status,
+				status_changed_at, false AS "shareable!: bool"
+			FROM link_history
+			WHERE domain = $1 AND id = $2
+			ORDER BY replaced_at DESC;
+			"#,
+			domain,
+			id,
+		)
+			.fetch_all(pool)
 			.await?;
 
 
-		Ok(link_row.is_some())
+		Ok(links)
+	}
+
+	/// Disables the link for the given reason, or enables it again if there is none.
+	/// Banned links are left alone, bans are permanent. Returns whether there was a link to change.
+	async fn set_disabled(domain: &str, id: &str, reason: Option<&str>, pool: &Pool<Sqlite>) -> Result<bool, ShortyError> {
+		let status = if reason.is_some() { LinkState::Disabled } else { LinkState::Active }.as_str();
+		let now = time_now();
+		let result = sqlx::query!(
+			r#"
+			UPDATE links
+			SET disabled_reason = $1, status = $2, status_changed_at = $3
+			WHERE domain = $4 AND id = $5 AND status != 'deleted' AND (disabled_reason IS NULL OR disabled_reason != 'banned');
+			"#,
+			reason,
+			status,
+			now,
+			domain,
+			id,
+		)
+			.execute(pool)
+			.await?;
+
+
+		Ok(result.rows_affected() > 0)
+	}
+
+	/// The point in time (in milliseconds) after which the link expires, if it expires based on time.
+	#[must_use]
+	pub fn expires_at(&self) -> Option<i64> {
+		(self.valid_for != 0).then_some(self.starts_at() + self.valid_for)
+	}
+
+	/// When the link went or goes live, in milliseconds. `valid_for` counts from here.
+	#[must_use]
+	pub fn starts_at(&self) -> i64 {
+		self.not_before.unwrap_or(self.created_at)
+	}
+
+	/// Whether the link isn't live yet.
+	#[must_use]
+	pub fn is_pending(&self) -> bool {
+		self.not_before.is_some_and(|not_before| not_before > time_now())
 	}
 
 	/// Formats self, according to the options set in the config file.
+	/// If the domain of the link isn't configured anymore, its host is used as the public URL.
 	#[must_use]
 	pub fn formatted(&self) -> String {
-		format!("{}/{}", CONFIG.public_url, self.id)
+		let public_url = Domain::by_name(self.domain.as_str())
+			.map_or_else(|| format!("http://{}", self.domain), |domain| domain.public_url);
+
+
+		format!("{public_url}/{}", self.id)
+	}
+}
+
+/// Checks the length of a custom ID and replaces characters that aren't allowed in URLs.
+fn custom_id(id: &str) -> Result<String, ShortyError> {
+	if id.len() > CONFIG.load().max_custom_id_length {
+		return Err(ShortyError::CustomIDExceedsMaxLength);
 	}
+
+
+	Ok(replace_illegal_url_chars(id))
+}
+
+/// Pushes a condition matching expired links, the SQL equivalent of [`Link::is_expired`].
+fn push_expired_condition(query: &mut QueryBuilder<Sqlite>, now: i64) {
+	query.push("(valid_for < 0 OR (valid_for > 0 AND ")
+		.push_bind(now)
+		.push(" - COALESCE(not_before, created_at) > valid_for) OR max_uses < 0 OR (max_uses > 0 AND invocations >= max_uses))");
+}
+
+/// The lowercase host of a URL, if it has one.
+fn target_host(url: &str) -> Option<String> {
+	url::Url::parse(url).ok()?.host_str().map(str::to_lowercase)
+}
+
+/// Errors with [`ShortyError::BlockedDomain`] if the host of the URL was banned.
+async fn ensure_host_not_banned(url: &str, conn: &mut SqliteConnection) -> Result<(), ShortyError> {
+	let Some(host) = target_host(url) else {
+		return Ok(());
+	};
+
+	if is_banned_host(host.as_str(), conn).await? {
+		return Err(ShortyError::BlockedDomain(host));
+	}
+
+
+	Ok(())
+}
+
+/// Escapes the wildcards of `LIKE` patterns, for patterns with `ESCAPE '\'`.
+fn escape_like(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Keeps the `limit` and `offset` of a page of the admin API within `max_page_size` and the table.
+/// SQLite would take a negative limit as no limit at all.
+fn page(limit: i64, offset: i64) -> (i64, i64) {
+	(limit.clamp(0, i64::from(CONFIG.load().max_page_size)), offset.max(0))
 }
 
 pub struct LinkStore {
@@ -246,20 +1037,354 @@ impl LinkStore {
 		Self { db }
 	}
 
-	/// Retrieves a link with the provided ID, if it exists.
-	pub async fn get(&self, id: &str) -> Option<Link> {
-		let link = Link::from_id(id, &self.db).await;
+	/// Looks up the link the path leads to on the domain to redirect to it.
+	/// That is the link with exactly this ID, or else the prefix or template link with the longest ID the path starts with.
+	/// Aliases lead to the link they belong to.
+	/// This **does not** count as a use, the target has to be [checked](LinkStore::check_target) and
+	/// the use [counted](LinkStore::count_use) once it is known where the visitor is sent.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn get(&self, domain: &Domain, path: &str) -> Result<Lookup, ShortyError> {
+		let Some((link, rest)) = self.resolve(domain, path).await? else {
+			return Ok(Lookup::NotFound);
+		};
+
+		match link.state() {
+			LinkState::Active if link.is_pending() => {
+				debug!("{} got requested but isn't live yet.", link.id);
+				Ok(Lookup::Pending(link))
+			}
+			LinkState::Active => Ok(Lookup::Active(link, rest.to_owned())),
+			LinkState::Expired => {
+				debug!("{} got requested but is expired.", link.id);
+				link.mark_expired(&self.db).await?;
+				Ok(Lookup::Gone(link))
+			}
+			LinkState::Disabled => {
+				debug!("{} got requested but is disabled.", link.id);
+				Ok(Lookup::Gone(link))
+			}
+			LinkState::Deleted => Ok(Lookup::NotFound),
+		}
+	}
+
+	/// Finds the link with exactly the path as ID, or the prefix or template link with the longest ID
+	/// that is followed by a `/` in the path. Returns the link and the rest of the path after its ID.
+	/// Paths with more than [`MAX_PATH_SEGMENTS`] segments aren't looked up.
+	async fn resolve<'a>(&self, domain: &Domain, path: &'a str) -> Result<Option<(Link, &'a str)>, ShortyError> {
+		if path.split('/').count() > MAX_PATH_SEGMENTS {
+			debug!("Not looking up {path}, it has more than {MAX_PATH_SEGMENTS} segments.");
+			return Ok(None);
+		}
+
+		if let Some(link) = self.find(domain, path).await? {
+			return Ok(Some((link, "")));
+		}
+
+		for (end, _) in path.rmatch_indices('/') {
+			let (id, rest) = path.split_at(end);
+			if id.is_empty() {
+				continue;
+			}
 
-		if let Ok(Some(link)) = link {
-			if !link.is_expired() {
-				return Some(link);
+			if let Some(link) = self.find(domain, id).await? {
+				if link.prefix || link.template {
+					return Ok(Some((link, rest)));
+				}
 			}
+		}
+
+
+		Ok(None)
+	}
+
+	/// Retrieves a link with the provided ID, if it exists, even if it is expired or disabled.
+	/// If no link has the ID, but an alias does, the link the alias belongs to is returned.
+	/// This **does not** count as a use of the link.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn find(&self, domain: &Domain, id: &str) -> Result<Option<Link>, ShortyError> {
+		if let Some(link) = Link::from_id_no_invocation(domain.name.as_str(), id, &self.db).await? {
+			return Ok(Some(link));
+		}
+
+		let normalized_id = normalize_id(id, &CONFIG.load());
+		let Some(alias) = Alias::find(domain.name.as_str(), id, normalized_id.as_str(), &mut *self.db.acquire().await?).await? else {
+			return Ok(None);
+		};
+
+
+		Link::from_id_no_invocation(domain.name.as_str(), alias.link_id.as_str(), &self.db).await
+	}
+
+	/// Retrieves up to `limit` links, skipping the first `offset` ones.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn list(&self, limit: i64, offset: i64) -> Result<Vec<Link>, ShortyError> {
+		Link::list(limit, offset, &self.db).await
+	}
+
+	/// Retrieves up to `limit` links matching the filter, newest first, skipping the first `offset` ones.
+	/// Also returns how many links match the filter in total.
+	///
+	/// # Errors
+	///
+	/// Errors if the domain of the filter isn't configured or if there is some problem communicating with the database.
+	pub async fn search(&self, filter: &LinkFilter, limit: i64, offset: i64) -> Result<(Vec<Link>, i64), ShortyError> {
+		let domain = filter.domain.as_deref().map(Domain::requested).transpose()?;
+		let (limit, offset) = page(limit, offset);
+
+
+		Link::search(domain.as_ref().map(|domain| domain.name.as_str()), filter, limit, offset, &self.db).await
+	}
+
+	/// Counts the links, their uses, the open reports and the banned domains.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn totals(&self) -> Result<Totals, ShortyError> {
+		Link::totals(&self.db).await
+	}
+
+	/// Disables a link, or the link the alias with the ID belongs to, until it is [reinstated](LinkStore::reinstate).
+	/// Returns whether there was a link to disable.
+	///
+	/// # Errors
+	///
+	/// Errors if the link is banned, since that would lift the ban,
+	/// or if there is some problem communicating with the database.
+	pub async fn disable(&self, domain: &Domain, id: &str) -> Result<bool, ShortyError> {
+		let Some(link) = self.find(domain, id).await? else {
+			return Ok(false);
+		};
+		if link.disabled_reason.as_deref() == Some(DISABLED_BANNED) {
+			return Err(ShortyError::LinkBanned(link.id));
+		}
+
+
+		Link::set_disabled(link.domain.as_str(), link.id.as_str(), Some(DISABLED_BY_ADMIN), &self.db).await
+	}
+
+	/// Retrieves the links the ID pointed to before, most recently replaced first.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn history(&self, domain: &Domain, id: &str) -> Result<Vec<Link>, ShortyError> {
+		Link::history(domain.name.as_str(), id, &self.db).await
+	}
+
+	/// Marks the link with the provided ID, or the link the alias with the ID belongs to, as deleted.
+	/// Returns whether there was a link to delete.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn delete(&self, domain: &Domain, id: &str) -> Result<bool, ShortyError> {
+		let Some(link) = self.find(domain, id).await? else {
+			return Ok(false);
+		};
+
+
+		Link::delete(link.domain.as_str(), link.id.as_str(), &self.db).await
+	}
+
+	/// Counts a use of the link and of the variant the visitor is sent to, if any,
+	/// once the visitor is about to be redirected.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn count_use(&self, link: &Link, variant: Option<&Variant>) -> Result<(), ShortyError> {
+		link.count_use(&self.db).await?;
+		if let Some(variant) = variant {
+			variant.count_use(&self.db).await?;
+		}
+
+
+		Ok(())
+	}
+
+	/// Picks the variant a visitor of the link is sent to, if the link has variants.
+	/// `sticky` is the position of the variant the visitor got before, which they get again if it still exists.
+	/// The visit isn't counted yet, see [`LinkStore::count_use`].
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn choose_variant(&self, link: &Link, sticky: Option<i64>) -> Result<Option<Variant>, ShortyError> {
+		if link.variant_count == 0 {
+			return Ok(None);
+		}
+
+		let variants = Variant::for_link(link.domain.as_str(), link.id.as_str(), &self.db).await?;
+		let Some(variant) = pick(variants.as_slice(), sticky.filter(|_| link.sticky)) else {
+			return Ok(None);
+		};
+
+
+		Ok(Some(variant.clone()))
+	}
+
+	/// Retrieves the variants of the link with the ID, with how often visitors were sent to each.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn variants(&self, domain: &Domain, id: &str) -> Result<Vec<Variant>, ShortyError> {
+		Variant::for_link(domain.name.as_str(), id, &self.db).await
+	}
 
-			debug!("{} got requested but is expired.", link.id);
+	/// Adds an alias with the ID `alias` to the link with the ID `link_id`.
+	/// Returns `None` if there is no such link.
+	///
+	/// # Errors
+	///
+	/// Errors if the ID of the alias is already taken, too long,
+	/// or if there is some problem communicating with the database.
+	pub async fn add_alias(&self, domain: &Domain, link_id: &str, alias: &str) -> Result<Option<Alias>, ShortyError> {
+		let Some(link) = Link::from_id_no_invocation(domain.name.as_str(), link_id, &self.db).await? else {
+			return Ok(None);
+		};
+
+		let id = custom_id(alias)?;
+		let normalized_id = normalize_id(id.as_str(), &CONFIG.load());
+		let mut transaction = self.db.begin().await?;
+		let existing = Link::free_id(link.domain.as_str(), id.as_str(), normalized_id.as_str(), time_now(), &mut transaction).await?;
+		for stale in existing {
+			Link::archive(stale.domain.as_str(), stale.id.as_str(), &mut transaction).await?;
+		}
+		let alias = Alias::create(link.domain.as_str(), id.as_str(), normalized_id.as_str(), link.id.as_str(), &mut transaction).await?;
+		transaction.commit().await?;
+
+
+		Ok(Some(alias))
+	}
+
+	/// Removes the alias with the ID `alias` from the link with the ID `link_id`.
+	/// Returns whether there was such an alias.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn remove_alias(&self, domain: &Domain, link_id: &str, alias: &str) -> Result<bool, ShortyError> {
+		Alias::delete(domain.name.as_str(), link_id, alias, &self.db).await
+	}
+
+	/// Retrieves the aliases of the link with the ID, oldest first.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn aliases(&self, domain: &Domain, id: &str) -> Result<Vec<Alias>, ShortyError> {
+		Alias::for_link(domain.name.as_str(), id, &self.db).await
+	}
+
+	/// Checks whether links may still redirect to `url`.
+	/// The config or the banned domains might have changed since the link was created.
+	///
+	/// # Errors
+	///
+	/// Errors with [`ShortyError::BlockedDomain`] if the host of the URL is blocked or banned,
+	/// or if there is some problem communicating with the database.
+	pub async fn check_target(&self, url: &str) -> Result<(), ShortyError> {
+		CONFIG.load().domain_filter.check(url)?;
+
+
+		ensure_host_not_banned(url, &mut *self.db.acquire().await?).await
+	}
+
+	/// Reports a link. Once the configured number of distinct reporters reported it, the link gets disabled.
+	/// Returns whether the link is disabled now.
+	///
+	/// # Errors
+	///
+	/// Errors if the reason isn't valid or if there is some problem communicating with the database.
+	pub async fn report(&self, link: &Link, reason: &str, reporter: &str) -> Result<bool, ShortyError> {
+		let reporters = Report::create(link, reason, reporter, &self.db).await?;
+		if link.disabled_reason.is_some() {
+			return Ok(true);
+		}
+
+		let threshold = i64::from(CONFIG.load().reports_to_disable);
+		if threshold == 0 || reporters < threshold {
+			return Ok(false);
+		}
+
+		info!("Disabling {} after reports from {reporters} reporters.", link.formatted());
+		Link::set_disabled(link.domain.as_str(), link.id.as_str(), Some(DISABLED_REPORTED), &self.db).await?;
+
+
+		Ok(true)
+	}
+
+	/// Retrieves up to `limit` reports, newest first, skipping the first `offset` ones.
+	/// Resolved reports are only included if `resolved` is set.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn reports(&self, resolved: bool, limit: i64, offset: i64) -> Result<Vec<Report>, ShortyError> {
+		let (limit, offset) = page(limit, offset);
+		Report::list(resolved, limit, offset, &self.db).await
+	}
+
+	/// Enables a disabled link again and resolves its reports.
+	/// Returns whether there was a link to reinstate.
+	///
+	/// # Errors
+	///
+	/// Errors if the link is banned, since bans are permanent,
+	/// or if there is some problem communicating with the database.
+	pub async fn reinstate(&self, domain: &Domain, id: &str) -> Result<bool, ShortyError> {
+		let Some(link) = self.find(domain, id).await? else {
+			return Ok(false);
+		};
+		if link.disabled_reason.as_deref() == Some(DISABLED_BANNED) {
+			return Err(ShortyError::LinkBanned(link.id));
+		}
+
+		if !Link::set_disabled(link.domain.as_str(), link.id.as_str(), None, &self.db).await? {
+			return Ok(false);
+		}
+		Report::resolve(link.domain.as_str(), link.id.as_str(), &self.db).await?;
+
+
+		Ok(true)
+	}
+
+	/// Permanently disables a link and resolves its reports.
+	/// If `ban_host` is set, the host the link redirects to is banned as well, so no link can redirect to it anymore.
+	/// Returns the banned link, if there was one.
+	///
+	/// # Errors
+	///
+	/// Errors if there is some problem communicating with the database.
+	pub async fn ban(&self, domain: &Domain, id: &str, ban_target_host: bool) -> Result<Option<Link>, ShortyError> {
+		let Some(mut link) = self.find(domain, id).await? else {
+			return Ok(None);
+		};
+
+		Link::set_disabled(link.domain.as_str(), link.id.as_str(), Some(DISABLED_BANNED), &self.db).await?;
+		Report::resolve(link.domain.as_str(), link.id.as_str(), &self.db).await?;
+		link.disabled_reason = Some(DISABLED_BANNED.to_owned());
+
+		if ban_target_host {
+			if let Some(host) = target_host(link.redirect_to.as_str()) {
+				info!("Banning {host}, which {} redirects to.", link.formatted());
+				ban_host(host.as_str(), &self.db).await?;
+			}
 		}
 
 
-		None
+		Ok(Some(link))
 	}
 
 	/// Creates a shortened link with default settings.
@@ -267,52 +1392,229 @@ impl LinkStore {
 	/// # Errors
 	///
 	/// Returns an error if the underlying [`Link::new`] call fails.
-	pub async fn create_link(&self, link: String) -> Result<Link, ShortyError> {
-		Link::new(link, &self.db).await
+	pub async fn create_link(&self, link: String, domain: &Domain) -> Result<Link, ShortyError> {
+		let mut transaction = self.db.begin().await?;
+		let link = Link::new(link, domain, &mut transaction).await?;
+		transaction.commit().await?;
+
+
+		Ok(link)
 	}
 
-	/// Creates a shortened link with custom settings.
+	/// Creates a shortened link with custom settings in a transaction,
+	/// so a link whose variants can't be created isn't left behind without them.
 	///
 	/// # Errors
 	///
-	/// Returns an error if the underlying [`Link::new_with_config`] call fails.
+	/// Returns an error if the underlying [`Link::new_with_config`] call fails
+	/// or the transaction can't be started or committed.
 	pub async fn create_link_with_config(
 		&self,
 		link_config: LinkConfig,
+		domain: &Domain,
 	) -> Result<Link, ShortyError> {
-		Link::new_with_config(link_config, &self.db).await
+		let mut transaction = self.db.begin().await?;
+		let link = Link::new_with_config(link_config, domain, &mut transaction).await?;
+		transaction.commit().await?;
+
+
+		Ok(link)
 	}
 
-	/// This function deletes stale links from the database.
+	/// Creates shortened links with custom settings in a single transaction.
+	/// Every link is created in a savepoint of its own, so links that can't be created don't keep the others
+	/// from being created. Returns the result for every link, in the order of the configs.
 	///
 	/// # Errors
 	///
-	/// Errors if theres a problem executing the SQL queries.
-	pub async fn clean(&self) -> Result<(), ShortyError> {
-		debug!("Clearing stale links");
+	/// Errors if the transaction can't be started or committed.
+	pub async fn create_links_with_config(
+		&self,
+		link_configs: Vec<LinkConfig>,
+		domain: &Domain,
+	) -> Result<Vec<Result<Link, ShortyError>>, ShortyError> {
+		let mut transaction = self.db.begin().await?;
+		let mut results = Vec::with_capacity(link_configs.len());
+		for link_config in link_configs {
+			let mut savepoint = transaction.begin().await?;
+			let result = Link::new_with_config(link_config, domain, &mut savepoint).await;
+			if result.is_ok() {
+				savepoint.commit().await?;
+			} else {
+				savepoint.rollback().await?;
+			}
+			results.push(result);
+		}
+		transaction.commit().await?;
 
-		let res = sqlx::query!("SELECT COUNT(*) AS num_before FROM links").fetch_one(&self.db).await?;
-		let num_before = res.num_before;
 
-		let now = time_now();
-		sqlx::query!(
+		Ok(results)
+	}
+
+	/// Exports all links, whatever their state, in the format.
+	/// The links are read page by page while the returned stream is consumed.
+	pub fn export(&self, format: Format) -> impl Stream<Item = Result<Bytes, ShortyError>> + 'static {
+		let pool = self.db.clone();
+
+
+		stream::try_unfold(Some((String::new(), String::new())), move |after| {
+			let pool = pool.clone();
+			async move {
+				let Some((after_domain, after_id)) = after else {
+					return Ok(None);
+				};
+
+				let links = Link::export_page(after_domain.as_str(), after_id.as_str(), EXPORT_PAGE_SIZE, &pool).await?;
+				let next = (links.len() == usize::try_from(EXPORT_PAGE_SIZE).unwrap_or(usize::MAX))
+					.then(|| links.last().map(|link| (link.domain.clone(), link.id.clone())))
+					.flatten();
+				let first = after_domain.is_empty() && after_id.is_empty();
+				let exported: Vec<ExportedLink> = links.into_iter().map(ExportedLink::from).collect();
+
+
+				Ok(Some((transfer::write(format, exported.as_slice(), first)?, next)))
+			}
+		})
+	}
+
+	/// Imports links in a single transaction, each in a savepoint of its own like [`LinkStore::create_links_with_config`].
+	/// Returns the outcome for every link, in the order of the input.
+	/// A `dry_run` rolls the transaction back in the end, so it only reports what the import would do.
+	///
+	/// # Errors
+	///
+	/// Errors if the transaction can't be started or committed.
+	pub async fn import(&self, links: Vec<(u64, Result<ExportedLink, String>)>, policy: ConflictPolicy, dry_run: bool) -> Result<Vec<ImportResult>, ShortyError> {
+		let mut transaction = self.db.begin().await?;
+		let mut results = Vec::with_capacity(links.len());
+		for (line, exported) in links {
+			let exported = match exported {
+				Ok(exported) => exported,
+				Err(why) => {
+					results.push(ImportResult::failed(line, String::new(), why));
+					continue;
+				}
+			};
+
+			let mut savepoint = transaction.begin().await?;
+			let result = match transfer::import(&exported, policy, &mut savepoint).await {
+				Ok((outcome, link)) => {
+					savepoint.commit().await?;
+					let new_id = (outcome == ImportOutcome::Renamed).then_some(link.id);
+					ImportResult { line, id: exported.id, outcome, new_id, error: None }
+				}
+				Err(why) => {
+					savepoint.rollback().await?;
+					let outcome = match why {
+						ShortyError::LinkConflict | ShortyError::NormalizedIdConflict(_) | ShortyError::IdRecentlyExpired(_) => ImportOutcome::Skipped,
+						_ => ImportOutcome::Failed,
+					};
+					ImportResult { line, id: exported.id, outcome, new_id: None, error: Some(why.to_string()) }
+				}
+			};
+			results.push(result);
+		}
+		if dry_run {
+			transaction.rollback().await?;
+		} else {
+			transaction.commit().await?;
+		}
+
+
+		Ok(results)
+	}
+
+	/// Normalizes the IDs of all links and aliases again with the `*_ids` options of the config, so links created
+	/// before the options changed can be found with IDs that are only the same after the new normalization.
+	/// Nothing is changed if two links or aliases on a domain would get the same ID. Returns how many IDs changed.
+	///
+	/// # Errors
+	///
+	/// Errors with the colliding IDs as config problems, or if theres a problem executing the SQL queries.
+	pub async fn normalize_ids(&self, config: &Config) -> Result<u64, ShortyError> {
+		let mut transaction = self.db.begin().await?;
+		let links = sqlx::query!(
 			r#"
-			DELETE FROM links
-			WHERE max_uses != 0 AND invocations > max_uses
-			OR created_at + valid_for < $1
-			"#,
-			now
+			SELECT domain, id, normalized_id, status = 'deleted' AS "deleted!: bool" FROM links;
+			"#
+		)
+			.fetch_all(&mut *transaction)
+			.await?;
+		let aliases = sqlx::query!(
+			r#"
+			SELECT domain, id, normalized_id FROM aliases;
+			"#
 		)
-			.execute(&self.db)
+			.fetch_all(&mut *transaction)
 			.await?;
 
-		let res = sqlx::query!("SELECT COUNT(*) AS num_after FROM links").fetch_one(&self.db).await?;
-		let num_after = res.num_after;
+		let ids = links.into_iter()
+			.map(|link| (link.domain, link.id, link.normalized_id, link.deleted, false))
+			.chain(aliases.into_iter().map(|alias| (alias.domain, alias.id, alias.normalized_id, false, true)));
+		let mut taken = HashMap::new();
+		let mut problems = Vec::new();
+		let mut changed = 0;
+		for (domain, id, current, deleted, alias) in ids {
+			let normalized = normalize_id(id.as_str(), config);
+			// Deleted links can't be opened anymore, so they don't get in the way of others.
+			if !deleted {
+				if let Some(other) = taken.insert((domain.clone(), normalized.clone()), id.clone()) {
+					let on = if domain.is_empty() { String::new() } else { format!(" on {domain}") };
+					problems.push(ConfigProblem {
+						key: normalization_option(other.as_str(), id.as_str()),
+						message: format!("makes the IDs {other} and {id}{on} the same ID."),
+						suggestion: Some("Delete or rename one of the links, or remove the alias, before turning it on.".to_owned()),
+					});
+				}
+			}
+			if normalized == current {
+				continue;
+			}
 
-		let delta = num_before - num_after;
-		debug!("Size before cleaning: {num_before}. After cleaning: {num_after}. Removed elements: {delta}");
+			if alias {
+				sqlx::query!("UPDATE aliases SET normalized_id = $1 WHERE domain = $2 AND id = $3;", normalized, domain, id)
+					.execute(&mut *transaction)
+					.await?;
+			} else {
+				sqlx::query!("UPDATE links SET normalized_id = $1 WHERE domain = $2 AND id = $3;", normalized, domain, id)
+					.execute(&mut *transaction)
+					.await?;
+			}
+			changed += 1;
+		}
+
+		if !problems.is_empty() {
+			transaction.rollback().await?;
+			return Err(ShortyError::InvalidConfig(ConfigProblems(problems)));
+		}
+		transaction.commit().await?;
 
 
-		Ok(())
+		Ok(changed)
+	}
+
+	/// Marks links that ran out of time or uses as expired.
+	/// Returns how many links were marked.
+	///
+	/// # Errors
+	///
+	/// Errors if theres a problem executing the SQL queries.
+	pub async fn clean(&self) -> Result<u64, ShortyError> {
+		debug!("Marking stale links as expired");
+
+		// Links that ran out of time expired when their time was up, links that ran out of uses expire now.
+		let now = time_now();
+		let mut query = QueryBuilder::new("UPDATE links SET status = 'expired', status_changed_at = CASE WHEN valid_for != 0 AND COALESCE(not_before, created_at) + valid_for <= ");
+		query.push_bind(now)
+			.push(" THEN COALESCE(not_before, created_at) + valid_for ELSE ")
+			.push_bind(now)
+			.push(" END WHERE status = 'active' AND ");
+		push_expired_condition(&mut query, now);
+		let expired = query.build().execute(&self.db).await?.rows_affected();
+
+		debug!("Marked {expired} links as expired.");
+
+
+		Ok(expired)
 	}
 }
@@ -1,139 +1,168 @@
 #![allow(clippy::module_name_repetitions)]
 #![allow(clippy::module_inception)]
 
-use std::io::{Read, Write};
 use std::path::Path;
+use std::process::ExitCode;
 use std::time::Duration;
 
 use actix_cors::Cors;
 use actix_web::{App, HttpServer, web};
-use lazy_static::lazy_static;
-use sqlx::migrate::MigrateDatabase;
-use sqlx::Sqlite;
-use sqlx::sqlite::{SqliteAutoVacuum, SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use clap::Parser;
+use sqlx::{Pool, Sqlite};
 use tracing::{debug, error, info, Level};
 use tracing_subscriber::EnvFilter;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::config::Config;
-use crate::config::SAMPLE_CONFIG;
-use crate::endpoints::{ApiDoc, create_shortened, create_shortened_custom, get_config, get_favicon, get_shortened, index, serve_file};
+use crate::cli::{Cli, Command};
+use crate::config::{CONFIG, Config};
+use crate::admin::{add_link_alias, ban_link, delete_link, disable_link, export_links, get_link_aliases, get_link_history, get_link_variants, get_totals, import_links, list_links, list_reports, reinstate_link, remove_link_alias};
+use crate::endpoints::{ApiDoc, create_shortened, create_shortened_batch, create_shortened_custom, get_config, get_favicon, get_shortened, index, report_link, serve_file};
 use crate::error::ShortyError;
 use crate::link::{LinkConfig, LinkStore};
-use crate::util::ensure_http_prefix;
+use crate::shutdown::{close_database, shutdown_signal};
 
 pub mod util;
 pub mod link;
 pub mod config;
+pub mod config_validation;
 pub mod error;
 pub mod endpoints;
+pub mod shutdown;
+pub mod cli;
+pub mod database;
+pub mod domain;
+pub mod domain_filter;
+pub mod report;
+pub mod admin;
+pub mod redirect;
+pub mod template;
+pub mod rules;
+pub mod variant;
+pub mod alias;
+pub mod transfer;
+pub mod importers;
 
 const CLEAN_SLEEP_DURATION: Duration = Duration::from_secs(60 * 60);
 
-lazy_static! {
-	static ref CONFIG: Config = {
-		let config_location = std::env::var("SHORTY_CONFIG")
-			.unwrap_or_else(|_| "./config.toml".to_owned());
-		let path = Path::new(&config_location);
-
-		if !path.exists() {
-			let mut file = std::fs::File::create(path).expect("Failed to create sample config file");
-			file.write_all(SAMPLE_CONFIG.as_bytes()).expect("Couldn't write the sample config file");
-
-			error!(
-				"You have to configure the config file. A sample config was created at {}",
-				config_location
-			);
-			std::process::exit(1);
-		}
-
-		let mut file = std::fs::File::open(path).expect("Failed to open config file.");
-		let mut content = String::new();
-		file.read_to_string(&mut content).expect("Failed to read config file.");
+/// Exit code for an invalid config, `EX_CONFIG` from sysexits.h.
+const EXIT_INVALID_CONFIG: u8 = 78;
 
-
-		Config::new(content.as_str()).expect("Failed to parse config")
-	};
+#[tokio::main]
+async fn main() -> ExitCode {
+	match run().await {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(why @ (ShortyError::InvalidConfig(_) | ShortyError::ConfigParse(_))) => {
+			eprintln!("{why}");
+			ExitCode::from(EXIT_INVALID_CONFIG)
+		}
+		Err(why) => {
+			eprintln!("Error: {why}");
+			ExitCode::FAILURE
+		}
+	}
 }
 
-#[tokio::main]
-async fn main() -> Result<(), ShortyError> {
+async fn run() -> Result<(), ShortyError> {
 	if Path::new(".env").exists() {
 		dotenvy::dotenv()?;
 	}
 
+	let mut cli = Cli::parse();
+	let command = cli.command.take().unwrap_or(Command::Serve);
+
+	// The server logs what it does, the other commands only print their result.
+	let level = if matches!(command, Command::Serve) { Level::DEBUG } else { Level::WARN };
 	let env_filter = EnvFilter::from_default_env()
-		.add_directive(Level::INFO.into())
-		.add_directive("shorty=debug".parse().unwrap());
+		.add_directive(Level::INFO.min(level).into())
+		.add_directive(format!("shorty={level}").parse().unwrap());
 
 	tracing_subscriber::fmt()
 		.with_env_filter(env_filter)
 		.with_line_number(true)
 		.with_file(true)
+		.with_writer(std::io::stderr)
 		.init();
 
-	if !Sqlite::database_exists(CONFIG.database_location.as_str()).await? {
-		Sqlite::create_database(CONFIG.database_location.as_str()).await.expect("Couldn't create database file");
+	if let Command::Config(config_command) = &command {
+		return cli::run_config_command(config_command, &cli);
 	}
 
-	let db_options = SqliteConnectOptions::new()
-		.auto_vacuum(SqliteAutoVacuum::Full)
-		.journal_mode(SqliteJournalMode::Wal)
-		.filename(CONFIG.database_location.as_str());
-
-	let pool = SqlitePoolOptions::new()
-		.max_connections(5)
-		.min_connections(1)
-		.max_lifetime(Some(Duration::from_secs(60 * 60)))
-		.connect_with(db_options)
-		.await?;
-
-	sqlx::migrate!()
-		.run(&pool)
-		.await
-		.expect("Failed db schema migration.");
-
-	// Gracefully close the database connection(s) on CTRL+C
-	let pool_clone = pool.clone();
-	tokio::task::spawn(async move {
-		tokio::signal::ctrl_c().await.expect("Error awaiting SIGINT.");
-		info!("Received SIGINT, shutting down...");
-		debug!("Closing Database pool.");
-		pool_clone.close().await;
-		debug!("Closed Database pool.");
-	});
+	load_config(&cli)?;
 
-	let links = web::Data::new(LinkStore::new(pool.clone()));
-	let links_clone = links.clone();
+	let database_location = CONFIG.load().database_location.clone();
+	let pool = database::connect(database_location.as_str()).await?;
+	database::migrate(&pool).await?;
+	// Links created with other `*_ids` options have to be found with the current ones as well.
+	if let Err(why) = LinkStore::new(pool.clone()).normalize_ids(&CONFIG.load()).await {
+		close_database(&pool).await;
+		return Err(why);
+	}
 
-	tokio::task::spawn(async move {
-		loop {
-			if let Err(why) = links_clone.clean().await {
-				error!("{why}");
-			}
-			tokio::time::sleep(CLEAN_SLEEP_DURATION).await;
+	let result = match command {
+		Command::Serve => serve(pool.clone()).await,
+		Command::Migrate => {
+			println!("The database at {database_location} is up to date.");
+			Ok(())
 		}
-	});
+		Command::Links(links_command) => cli::run_links_command(links_command, &LinkStore::new(pool.clone())).await,
+		Command::Clean => LinkStore::new(pool.clone()).clean().await.map(|expired| {
+			println!("Marked {expired} stale links as expired.");
+		}),
+		Command::Config(_) => unreachable!("Handled before the config is loaded"),
+	};
+
+	close_database(&pool).await;
+
+
+	result
+}
 
-	let pool = web::Data::new(pool);
-	info!("Starting server at {}:{}", CONFIG.listen_url, CONFIG.port);
+/// Loads the config and makes it available through [`CONFIG`].
+fn load_config(cli: &Cli) -> Result<(), ShortyError> {
+	let config_file = cli.config_file()?;
+	match config_file {
+		Some(ref path) => info!("Loading config from {}", path.display()),
+		None => info!("There is no config file, using the defaults and environment variables."),
+	}
+
+	let config = Config::load(config_file.as_deref(), &cli.overrides)?;
+	config::init(config, config_file, cli.overrides.clone());
+
+
+	Ok(())
+}
+
+/// Runs the HTTP server until the process is asked to terminate.
+async fn serve(pool: Pool<Sqlite>) -> Result<(), ShortyError> {
+	let links = web::Data::new(LinkStore::new(pool.clone()));
+	let links_clone = links.clone();
+	let pool_data = web::Data::new(pool.clone());
+	// These are only read on startup, changing them requires a restart.
+	let (listen_url, port, shutdown_timeout) = {
+		let config = CONFIG.load();
+		(config.listen_url.clone(), config.port, config.shutdown_timeout)
+	};
+	info!("Starting server at {listen_url}:{port}");
 
 	let openapi = ApiDoc::openapi();
 
-	HttpServer::new(move || {
+	let server = HttpServer::new(move || {
 		let json_config = web::JsonConfig::default()
-			.limit(CONFIG.max_json_size);
+			.limit(CONFIG.load().max_json_size);
+		// Imports are sent as plain bytes and get the same limit.
+		let payload_config = web::PayloadConfig::new(CONFIG.load().max_json_size);
 
 		let cors = Cors::default()
 			.allow_any_origin()
-			.allowed_methods(vec!["GET", "POST"]);
+			.allowed_methods(vec!["GET", "POST", "DELETE"]);
 
 		App::new()
 			.wrap(cors)
 			.app_data(json_config)
+			.app_data(payload_config)
 			.app_data(links.clone())
-			.app_data(pool.clone())
+			.app_data(pool_data.clone())
 			.service(
 				SwaggerUi::new("/documentation/{_:.*}").url("/documentation/openapi.json", openapi.clone())
 			)
@@ -141,16 +170,71 @@ async fn main() -> Result<(), ShortyError> {
 			.service(index)
 			.service(serve_file)
 			.service(get_favicon)
+			// The admin and report routes would otherwise be taken for link IDs or links to shorten.
+			.service(list_links)
+			.service(get_link_history)
+			.service(get_link_variants)
+			.service(get_link_aliases)
+			.service(add_link_alias)
+			.service(remove_link_alias)
+			.service(export_links)
+			.service(import_links)
+			.service(get_totals)
+			.service(disable_link)
+			.service(delete_link)
+			.service(list_reports)
+			.service(reinstate_link)
+			.service(ban_link)
+			// Like the routes above, reports have a prefix of their own, since `create_shortened` takes any path
+			// and has to come last. `/{id}/report` would shadow shortening URLs like `example.com/report`.
+			.service(report_link)
 			.service(get_shortened)
 			.service(create_shortened_custom)
+			.service(create_shortened_batch)
 			.service(create_shortened)
 	})
-		.bind((CONFIG.listen_url.as_str(), CONFIG.port))
-		.expect("Failed to bind port or listen address.")
-		.run()
-		.await
-		.expect("Error running the HTTP server.");
+		.bind((listen_url.as_str(), port))
+		.map_err(|why| ShortyError::Bind(format!("{listen_url}:{port}"), why))?
+		.shutdown_timeout(shutdown_timeout)
+		// We handle the signals ourselves, so the server and the rest of the application stop together.
+		.disable_signals()
+		.run();
+
+	// Tells the cleaner to stop once the HTTP server has shut down.
+	let (stop_cleaner, mut cleaner_stopped) = tokio::sync::watch::channel(false);
+	let cleaner = tokio::task::spawn(async move {
+		loop {
+			if let Err(why) = links_clone.clean().await {
+				error!("{why}");
+			}
 
+			tokio::select! {
+				() = tokio::time::sleep(CLEAN_SLEEP_DURATION) => {},
+				_ = cleaner_stopped.changed() => break,
+			}
+		}
+		debug!("Stopped the link cleaner.");
+	});
 
-	Ok(())
+	let config_watcher = tokio::task::spawn(config::watch_for_changes(LinkStore::new(pool.clone())));
+
+	let server_handle = server.handle();
+	tokio::task::spawn(async move {
+		shutdown_signal().await;
+		info!("No longer accepting connections, waiting up to {shutdown_timeout}s for in-flight requests.");
+		server_handle.stop(true).await;
+	});
+
+	let result = server.await;
+	info!("HTTP server stopped.");
+	config_watcher.abort();
+
+	// The cleaner might be in the middle of a run, let it finish before the pool gets closed.
+	let _ = stop_cleaner.send(true);
+	if let Err(why) = cleaner.await {
+		error!("The link cleaner panicked: {why}");
+	}
+
+
+	Ok(result?)
 }
@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use url::{form_urlencoded, Url};
+
+use crate::domain::Domain;
+use crate::error::ShortyError;
+use crate::link::Link;
+use crate::rules::{matching_target, Visitor};
+use crate::template::expand;
+
+/// How long browsers may cache permanent redirects, in seconds.
+/// Kept short enough that disabling or banning a link takes effect for people who opened it before.
+const PERMANENT_REDIRECT_MAX_AGE: u32 = 60 * 60 * 24;
+
+/// The status code a link redirects with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectType {
+	/// 301, permanent, clients may change the method to GET.
+	MovedPermanently,
+	/// 302, temporary, clients may change the method to GET. Some legacy clients only understand this one.
+	Found,
+	/// 307, temporary.
+	TemporaryRedirect,
+	/// 308, permanent.
+	PermanentRedirect,
+}
+
+impl RedirectType {
+	#[must_use]
+	pub fn status(self) -> StatusCode {
+		match self {
+			RedirectType::MovedPermanently => StatusCode::MOVED_PERMANENTLY,
+			RedirectType::Found => StatusCode::FOUND,
+			RedirectType::TemporaryRedirect => StatusCode::TEMPORARY_REDIRECT,
+			RedirectType::PermanentRedirect => StatusCode::PERMANENT_REDIRECT,
+		}
+	}
+
+	#[must_use]
+	pub fn is_permanent(self) -> bool {
+		matches!(self, RedirectType::MovedPermanently | RedirectType::PermanentRedirect)
+	}
+}
+
+impl TryFrom<i64> for RedirectType {
+	type Error = ShortyError;
+
+	fn try_from(code: i64) -> Result<Self, Self::Error> {
+		match code {
+			301 => Ok(RedirectType::MovedPermanently),
+			302 => Ok(RedirectType::Found),
+			307 => Ok(RedirectType::TemporaryRedirect),
+			308 => Ok(RedirectType::PermanentRedirect),
+			_ => Err(ShortyError::InvalidRedirectType(code)),
+		}
+	}
+}
+
+impl From<RedirectType> for i64 {
+	fn from(redirect_type: RedirectType) -> Self {
+		i64::from(redirect_type.status().as_u16())
+	}
+}
+
+/// The target of the first rule of the link the request matches, if any.
+#[must_use]
+pub fn rule_target(link: &Link, req: &HttpRequest) -> Option<String> {
+	let rules = link.rules();
+
+
+	matching_target(rules.as_slice(), &Visitor::from_request(req)).map(str::to_owned)
+}
+
+/// Where a use of the link on the domain by the request redirects to, starting from `base`.
+/// That is the target of a rule the request matched, of the variant it got or else the `redirect_to` of the link.
+/// Template links get their placeholders filled in, prefix links get the rest of the requested path appended
+/// to their path and the query of the request to their query. Other links always redirect to the same place.
+/// Afterwards the `append_query` parameters of the link, or the defaults of the domain, are added.
+#[must_use]
+pub fn location(link: &Link, domain: &Domain, base: &str, rest: &str, req: &HttpRequest) -> String {
+	let location = target(link, base, rest, req.query_string());
+	let parameters: Vec<(String, String)> = match link.append_query {
+		Some(ref parameters) => form_urlencoded::parse(parameters.as_bytes()).into_owned().collect(),
+		None => domain.default_append_query.clone().into_iter().collect(),
+	};
+
+
+	append_query(location, parameters.as_slice())
+}
+
+/// Adds the parameters to the query of the URL, except for those it has already.
+fn append_query(url: String, parameters: &[(String, String)]) -> String {
+	if parameters.is_empty() {
+		return url;
+	}
+
+	let Ok(mut parsed) = Url::parse(url.as_str()) else {
+		return url;
+	};
+
+	let existing: HashSet<String> = parsed.query_pairs().map(|(key, _)| key.into_owned()).collect();
+	let missing: Vec<_> = parameters.iter().filter(|(key, _)| !existing.contains(key)).collect();
+	if missing.is_empty() {
+		return url;
+	}
+
+	parsed.query_pairs_mut().extend_pairs(missing);
+
+
+	parsed.into()
+}
+
+/// The target of the link based on `base` before any parameters are appended, see [`location`].
+fn target(link: &Link, base: &str, rest: &str, query: &str) -> String {
+	if link.template {
+		return expand(base, rest, query);
+	}
+
+	if !link.prefix || (rest.is_empty() && query.is_empty()) {
+		return base.to_owned();
+	}
+
+	// Links are normalized when they are created, so this only fails for links from before that.
+	let Ok(mut url) = Url::parse(base) else {
+		return base.to_owned();
+	};
+
+	if !rest.is_empty() {
+		let path = format!("{}{rest}", url.path().trim_end_matches('/'));
+		url.set_path(path.as_str());
+	}
+
+	if !query.is_empty() {
+		let query = match url.query() {
+			Some(own) if !own.is_empty() => format!("{own}&{query}"),
+			_ => query.to_owned(),
+		};
+		url.set_query(Some(query.as_str()));
+	}
+
+
+	url.into()
+}
+
+/// A redirect to `location` that is never cached, e.g. for links that aren't live yet.
+#[must_use]
+pub fn temporary_redirect(location: &str) -> HttpResponse {
+	HttpResponse::TemporaryRedirect()
+		.insert_header((LOCATION, location))
+		.insert_header(CacheControl(vec![CacheDirective::NoStore]))
+		.finish()
+}
+
+/// Starts a redirect to `location` with the redirect type of the link.
+///
+/// Only permanent redirects of links without limits, rules or variants may be cached, and only for a day.
+/// Everything else must not be cached, so every use reaches the server and is counted,
+/// the link stops working once it expires and every visitor gets the target their rules or variant lead to.
+#[must_use]
+pub fn redirect(link: &Link, location: &str) -> HttpResponseBuilder {
+	let redirect_type = RedirectType::try_from(link.redirect_type).unwrap_or(RedirectType::TemporaryRedirect);
+	let cacheable = redirect_type.is_permanent()
+		&& link.max_uses == 0
+		&& link.valid_for == 0
+		&& link.rules.is_none()
+		&& link.variant_count == 0;
+	let cache_control = if cacheable {
+		CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(PERMANENT_REDIRECT_MAX_AGE)])
+	} else {
+		CacheControl(vec![CacheDirective::NoStore])
+	};
+
+
+	let mut response = HttpResponse::build(redirect_type.status());
+	response
+		.insert_header((LOCATION, location))
+		.insert_header(cache_control);
+
+
+	response
+}
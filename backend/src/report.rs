@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use utoipa::ToSchema;
+
+use crate::error::ShortyError;
+use crate::link::Link;
+use crate::util::time_now;
+
+/// The maximum length of the reason given in a report.
+pub const MAX_REASON_LENGTH: usize = 1_000;
+
+/// A report of a link, e.g. because it leads to a phishing site.
+/// All timestamps are in milliseconds.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Report {
+	pub id: i64,
+	/// The name of the domain the reported link belongs to.
+	pub domain: String,
+	pub link_id: String,
+	pub reason: String,
+	/// The address the report was sent from.
+	pub reporter: String,
+	pub created_at: i64,
+	/// When an admin reinstated or banned the link, if they did.
+	pub resolved_at: Option<i64>,
+}
+
+/// A report as it is sent by the public.
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({"reason": "This link leads to a phishing site."}))]
+pub struct ReportRequest {
+	/// Why the link should be taken down.
+	pub reason: String,
+}
+
+impl Report {
+	/// Stores a report of the link. A reporter that reported the link before replaces their earlier report.
+	/// Returns how many distinct reporters have open reports for the link.
+	///
+	/// # Errors
+	///
+	/// Errors if the reason is empty or too long, or if there is some problem communicating with the database.
+	pub(crate) async fn create(link: &Link, reason: &str, reporter: &str, pool: &Pool<Sqlite>) -> Result<i64, ShortyError> {
+		let reason = reason.trim();
+		if reason.is_empty() || reason.chars().count() > MAX_REASON_LENGTH {
+			return Err(ShortyError::InvalidReportReason);
+		}
+
+		let created_at = time_now();
+		sqlx::query!(
+			r#"
+			INSERT INTO reports (domain, link_id, reason, reporter, created_at)
+			VALUES ($1, $2, $3, $4, $5)
+			ON CONFLICT (domain, link_id, reporter)
+			DO UPDATE SET reason = excluded.reason, created_at = excluded.created_at, resolved_at = NULL;
+			"#,
+			link.domain,
+			link.id,
+			reason,
+			reporter,
+			created_at,
+		)
+			.execute(pool)
+			.await?;
+
+		let open = sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS reporters FROM reports
+			WHERE domain = $1 AND link_id = $2 AND resolved_at IS NULL;
+			"#,
+			link.domain,
+			link.id,
+		)
+			.fetch_one(pool)
+			.await?;
+
+
+		Ok(i64::from(open.reporters))
+	}
+
+	/// Retrieves reports, newest first. Only open reports are returned, unless `resolved` is set.
+	pub(crate) async fn list(resolved: bool, limit: i64, offset: i64, pool: &Pool<Sqlite>) -> Result<Vec<Self>, ShortyError> {
+		let reports = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT * FROM reports
+			WHERE $1 OR resolved_at IS NULL
+			ORDER BY created_at DESC, id DESC
+			LIMIT $2 OFFSET $3;
+			"#,
+			resolved,
+			limit,
+			offset,
+		)
+			.fetch_all(pool)
+			.await?;
+
+
+		Ok(reports)
+	}
+
+	/// Marks all open reports of the link as resolved.
+	pub(crate) async fn resolve(domain: &str, link_id: &str, pool: &Pool<Sqlite>) -> Result<(), ShortyError> {
+		let now = time_now();
+		sqlx::query!(
+			r#"
+			UPDATE reports
+			SET resolved_at = $1
+			WHERE domain = $2 AND link_id = $3 AND resolved_at IS NULL;
+			"#,
+			now,
+			domain,
+			link_id,
+		)
+			.execute(pool)
+			.await?;
+
+
+		Ok(())
+	}
+
+	/// Deletes all reports of the link, e.g. because the link itself was deleted.
+	pub(crate) async fn delete_for(domain: &str, link_id: &str, conn: &mut SqliteConnection) -> Result<(), ShortyError> {
+		sqlx::query!(
+			r#"
+			DELETE FROM reports
+			WHERE domain = $1 AND link_id = $2;
+			"#,
+			domain,
+			link_id,
+		)
+			.execute(&mut *conn)
+			.await?;
+
+
+		Ok(())
+	}
+}
+
+/// Bans a host, so no link can redirect to it anymore.
+pub(crate) async fn ban_host(host: &str, pool: &Pool<Sqlite>) -> Result<(), ShortyError> {
+	let now = time_now();
+	sqlx::query!(
+		r#"
+		INSERT OR IGNORE INTO banned_domains (host, banned_at)
+		VALUES ($1, $2);
+		"#,
+		host,
+		now,
+	)
+		.execute(pool)
+		.await?;
+
+
+	Ok(())
+}
+
+/// Whether the host was banned.
+pub(crate) async fn is_banned_host(host: &str, conn: &mut SqliteConnection) -> Result<bool, ShortyError> {
+	let row = sqlx::query!(
+		r#"
+		SELECT host FROM banned_domains WHERE host = $1;
+		"#,
+		host,
+	)
+		.fetch_optional(&mut *conn)
+		.await?;
+
+
+	Ok(row.is_some())
+}
@@ -0,0 +1,222 @@
+use actix_web::http::header::{ACCEPT_LANGUAGE, USER_AGENT};
+use actix_web::HttpRequest;
+use chrono::{Datelike, Local, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::error::ShortyError;
+use crate::template::normalize_template;
+use crate::util::normalize_target_url;
+
+/// The maximum number of rules a link may have.
+pub const MAX_RULES: usize = 50;
+
+/// A rule sending some visitors of a link somewhere else than its `redirect_to`.
+/// A rule matches if all of its conditions do, conditions that are left out always match.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(example = json!({"platforms": ["ios"], "redirect_to": "https://apps.apple.com/app/id0000000000"}))]
+pub struct Rule {
+	/// The visitor's operating system, taken from the User-Agent, is one of these.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub platforms: Vec<Platform>,
+	/// The visitor's preferred language, taken from the Accept-Language header, is one of these.
+	/// `de` matches all variants of German, `de-AT` only Austrian German.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub languages: Vec<String>,
+	/// The rule matches from this time of day on, as `HH:MM` in the server's time zone.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub from: Option<String>,
+	/// The rule matches until this time of day, as `HH:MM` in the server's time zone.
+	/// It may be before `from`, for windows spanning midnight.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub until: Option<String>,
+	/// The current day in the server's time zone is one of these.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub weekdays: Vec<Weekday>,
+	/// Where visitors matching the rule are sent to.
+	pub redirect_to: String,
+}
+
+/// An operating system, as told by the User-Agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+	Ios,
+	Android,
+	Windows,
+	Macos,
+	Linux,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+	Monday,
+	Tuesday,
+	Wednesday,
+	Thursday,
+	Friday,
+	Saturday,
+	Sunday,
+}
+
+impl From<chrono::Weekday> for Weekday {
+	fn from(weekday: chrono::Weekday) -> Self {
+		match weekday {
+			chrono::Weekday::Mon => Weekday::Monday,
+			chrono::Weekday::Tue => Weekday::Tuesday,
+			chrono::Weekday::Wed => Weekday::Wednesday,
+			chrono::Weekday::Thu => Weekday::Thursday,
+			chrono::Weekday::Fri => Weekday::Friday,
+			chrono::Weekday::Sat => Weekday::Saturday,
+			chrono::Weekday::Sun => Weekday::Sunday,
+		}
+	}
+}
+
+/// What rules are matched against, taken from a request.
+#[derive(Debug)]
+pub struct Visitor {
+	platform: Option<Platform>,
+	/// The preferred language in lower case, if there is one.
+	language: Option<String>,
+	/// Minutes since midnight.
+	minute_of_day: u32,
+	weekday: Weekday,
+}
+
+impl Visitor {
+	#[must_use]
+	pub fn from_request(req: &HttpRequest) -> Self {
+		let header = |name| req.headers().get(name).and_then(|value| value.to_str().ok());
+		let now = Local::now();
+
+
+		Self {
+			platform: header(USER_AGENT).and_then(platform),
+			language: header(ACCEPT_LANGUAGE).and_then(preferred_language),
+			minute_of_day: now.hour() * 60 + now.minute(),
+			weekday: now.weekday().into(),
+		}
+	}
+}
+
+impl Rule {
+	#[must_use]
+	pub fn matches(&self, visitor: &Visitor) -> bool {
+		let platform = self.platforms.is_empty()
+			|| visitor.platform.is_some_and(|platform| self.platforms.contains(&platform));
+		let language = self.languages.is_empty()
+			|| visitor.language.as_deref().is_some_and(|preferred| self.languages.iter().any(|language| language_matches(language, preferred)));
+		let weekday = self.weekdays.is_empty() || self.weekdays.contains(&visitor.weekday);
+		let from = self.from.as_deref().and_then(minute_of_day);
+		let until = self.until.as_deref().and_then(minute_of_day);
+		let time = match (from, until) {
+			(Some(from), Some(until)) if from <= until => (from..until).contains(&visitor.minute_of_day),
+			// The window spans midnight.
+			(Some(from), Some(until)) => visitor.minute_of_day >= from || visitor.minute_of_day < until,
+			(Some(from), None) => visitor.minute_of_day >= from,
+			(None, Some(until)) => visitor.minute_of_day < until,
+			(None, None) => true,
+		};
+
+
+		platform && language && weekday && time
+	}
+}
+
+/// The target of the first rule the visitor matches.
+#[must_use]
+pub fn matching_target<'a>(rules: &'a [Rule], visitor: &Visitor) -> Option<&'a str> {
+	rules.iter()
+		.find(|rule| rule.matches(visitor))
+		.map(|rule| rule.redirect_to.as_str())
+}
+
+/// Checks the rules and normalizes their targets like the target of the link itself,
+/// as templates if the link is a template link.
+///
+/// # Errors
+///
+/// Errors if there are too many rules, a rule has an invalid condition or an invalid target.
+pub fn normalize_rules(mut rules: Vec<Rule>, template: bool, config: &Config) -> Result<Vec<Rule>, ShortyError> {
+	if rules.len() > MAX_RULES {
+		return Err(ShortyError::InvalidRule(format!("a link can have at most {MAX_RULES} rules")));
+	}
+
+	for rule in &mut rules {
+		for time in [&rule.from, &rule.until].into_iter().flatten() {
+			if minute_of_day(time).is_none() {
+				return Err(ShortyError::InvalidRule(format!("'{time}' is not a time of day like 08:30")));
+			}
+		}
+
+		if rule.languages.iter().any(|language| language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')) {
+			return Err(ShortyError::InvalidRule("languages are written like 'de' or 'de-AT'".to_owned()));
+		}
+
+		rule.redirect_to = if template {
+			normalize_template(rule.redirect_to.as_str(), config)?
+		} else {
+			normalize_target_url(rule.redirect_to.as_str(), config)?
+		};
+	}
+
+
+	Ok(rules)
+}
+
+/// Parses a time of day like `08:30` into minutes since midnight.
+fn minute_of_day(time: &str) -> Option<u32> {
+	let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()?;
+
+
+	Some(time.hour() * 60 + time.minute())
+}
+
+/// Whether the language range of a rule matches the preferred language of a visitor.
+fn language_matches(range: &str, preferred: &str) -> bool {
+	let range = range.to_ascii_lowercase();
+
+
+	preferred == range || preferred.strip_prefix(range.as_str()).is_some_and(|rest| rest.starts_with('-'))
+}
+
+/// Tells the operating system from a User-Agent.
+fn platform(user_agent: &str) -> Option<Platform> {
+	// iOS and Android user agents mention the desktop systems they are based on, so they go first.
+	if ["iPhone", "iPad", "iPod"].iter().any(|device| user_agent.contains(device)) {
+		Some(Platform::Ios)
+	} else if user_agent.contains("Android") {
+		Some(Platform::Android)
+	} else if user_agent.contains("Windows") {
+		Some(Platform::Windows)
+	} else if user_agent.contains("Macintosh") || user_agent.contains("Mac OS X") {
+		Some(Platform::Macos)
+	} else if user_agent.contains("Linux") || user_agent.contains("X11") {
+		Some(Platform::Linux)
+	} else {
+		None
+	}
+}
+
+/// The language with the highest quality in an Accept-Language header, in lower case.
+/// The first one wins among languages with the same quality.
+fn preferred_language(accept_language: &str) -> Option<String> {
+	accept_language.split(',')
+		.filter_map(|entry| {
+			let mut parts = entry.split(';');
+			let language = parts.next()?.trim();
+			let quality = parts
+				.find_map(|parameter| parameter.trim().strip_prefix("q="))
+				.map_or(Some(1.0), |quality| quality.trim().parse::<f32>().ok())?;
+
+			(!language.is_empty() && language != "*" && quality > 0.0).then_some((language, quality))
+		})
+		.fold(None, |best: Option<(&str, f32)>, (language, quality)| match best {
+			Some((_, best_quality)) if best_quality >= quality => best,
+			_ => Some((language, quality)),
+		})
+		.map(|(language, _)| language.to_ascii_lowercase())
+}
@@ -0,0 +1,42 @@
+use sqlx::{Pool, Sqlite};
+use tracing::{debug, error, info};
+
+/// Resolves once the process is asked to terminate.
+/// That is either SIGINT (Ctrl+C) or, on unix systems, SIGTERM which is what
+/// systemd and container runtimes send.
+pub async fn shutdown_signal() {
+	let ctrl_c = async {
+		tokio::signal::ctrl_c().await.expect("Error awaiting SIGINT.");
+		info!("Received SIGINT, shutting down...");
+	};
+
+	#[cfg(unix)]
+	let terminate = async {
+		tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+			.expect("Error installing the SIGTERM handler.")
+			.recv()
+			.await;
+		info!("Received SIGTERM, shutting down...");
+	};
+
+	#[cfg(not(unix))]
+	let terminate = std::future::pending::<()>();
+
+	tokio::select! {
+		() = ctrl_c => {},
+		() = terminate => {},
+	}
+}
+
+/// Writes everything in the WAL back into the database file and closes the pool.
+/// Must only be called once nothing uses the pool anymore.
+pub async fn close_database(pool: &Pool<Sqlite>) {
+	debug!("Checkpointing the write-ahead log.");
+	if let Err(why) = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(pool).await {
+		error!("Failed to checkpoint the write-ahead log: {why}");
+	}
+
+	debug!("Closing Database pool.");
+	pool.close().await;
+	debug!("Closed Database pool.");
+}
@@ -0,0 +1,177 @@
+use std::fmt::Write;
+
+use url::form_urlencoded;
+use url::Url;
+
+use crate::config::Config;
+use crate::error::ShortyError;
+use crate::util::normalize_target_url;
+
+/// Stands in for placeholders while the target of a template link is normalized.
+/// It only contains characters that URLs never encode, so it comes out unchanged.
+const MARKER: &str = "shortyplaceholder";
+
+/// A part of the target of a template link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part<'a> {
+	Text(&'a str),
+	/// `{1}`, filled in with the first segment of the path after the ID.
+	Segment(&'a str, usize),
+	/// `{name}`, filled in with the query parameter `name`.
+	Parameter(&'a str, &'a str),
+}
+
+/// Splits the template into text and placeholders. Braces around anything but a number
+/// starting at 1 or a name made of letters, digits, `_` and `-` are just text.
+fn parse(template: &str) -> Vec<Part<'_>> {
+	let mut parts = Vec::new();
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		let Some(length) = rest[start..].find('}') else {
+			break;
+		};
+
+		let raw = &rest[start..=start + length];
+		let name = &raw[1..raw.len() - 1];
+		let placeholder = if let Ok(index) = name.parse::<usize>() {
+			(index > 0 && name.starts_with(|c: char| c.is_ascii_digit())).then_some(Part::Segment(raw, index))
+		} else if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+			&& name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-')) {
+			Some(Part::Parameter(raw, name))
+		} else {
+			None
+		};
+
+		match placeholder {
+			Some(placeholder) => {
+				parts.push(Part::Text(&rest[..start]));
+				parts.push(placeholder);
+			}
+			None => parts.push(Part::Text(&rest[..=start + length])),
+		}
+		rest = &rest[start + length + 1..];
+	}
+	parts.push(Part::Text(rest));
+
+
+	parts
+}
+
+/// Normalizes the target of a template link like [`normalize_target_url`] does for other links.
+/// Placeholders are kept as they are, but may only be used in the path, query and fragment,
+/// so whatever they are filled in with can't change where the link leads to.
+///
+/// # Errors
+///
+/// Errors if the template has no placeholders, has a placeholder before its path,
+/// or if [`normalize_target_url`] rejects it.
+pub fn normalize_template(template: &str, config: &Config) -> Result<String, ShortyError> {
+	let mut marked = String::with_capacity(template.len());
+	let mut placeholders = Vec::new();
+	for part in parse(template) {
+		match part {
+			Part::Text(text) => marked.push_str(text),
+			Part::Segment(raw, _) | Part::Parameter(raw, _) => {
+				let _ = write!(marked, "{MARKER}{}x", placeholders.len());
+				placeholders.push(raw);
+			}
+		}
+	}
+
+	if placeholders.is_empty() {
+		return Err(ShortyError::InvalidTemplate("it has no placeholders like {1} or {name}".to_owned()));
+	}
+
+	let normalized = normalize_target_url(marked.as_str(), config)?;
+	let url = Url::parse(normalized.as_str()).map_err(|why| ShortyError::InvalidUrl(why.to_string()))?;
+	if url.host_str().is_some_and(|host| host.contains(MARKER)) {
+		return Err(ShortyError::InvalidTemplate("placeholders can only be used in the path, query and fragment".to_owned()));
+	}
+
+
+	Ok(placeholders.iter().enumerate().fold(normalized, |normalized, (index, raw)| {
+		normalized.replace(format!("{MARKER}{index}x").as_str(), raw)
+	}))
+}
+
+/// Fills in the placeholders of the template with the segments of `rest`, the path after the ID,
+/// and the parameters of `query`. Placeholders without a value are left empty.
+/// The values are percent-encoded, so they can't add path segments, a query or a fragment.
+#[must_use]
+pub fn expand(template: &str, rest: &str, query: &str) -> String {
+	let segments: Vec<&str> = rest.split('/').filter(|segment| !segment.is_empty()).collect();
+	let parameters: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+
+	let mut expanded = String::with_capacity(template.len() + rest.len() + query.len());
+	for part in parse(template) {
+		match part {
+			Part::Text(text) => expanded.push_str(text),
+			Part::Segment(_, index) => encode_into(&mut expanded, segments.get(index - 1).copied().unwrap_or_default()),
+			Part::Parameter(_, name) => {
+				let value = parameters.iter().find(|(key, _)| key == name).map_or("", |(_, value)| value.as_str());
+				encode_into(&mut expanded, value);
+			}
+		}
+	}
+
+
+	expanded
+}
+
+/// Percent-encodes everything but unreserved characters.
+pub(crate) fn encode_into(target: &mut String, value: &str) {
+	for byte in value.bytes() {
+		if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+			target.push(char::from(byte));
+		} else {
+			let _ = write!(target, "%{byte:02X}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> Config {
+		toml::Table::new().try_into().unwrap()
+	}
+
+	#[test]
+	fn parses_placeholders() {
+		assert_eq!(parse("https://example.com/{1}?q={query}"), [
+			Part::Text("https://example.com/"),
+			Part::Segment("{1}", 1),
+			Part::Text("?q="),
+			Part::Parameter("{query}", "query"),
+			Part::Text(""),
+		]);
+		// Neither `{0}` and `{+1}` nor names with other characters are placeholders, an unclosed brace is just text.
+		assert_eq!(parse("/{0}/{+1}/{a b}/{-x}/{"), [Part::Text("/{0}"), Part::Text("/{+1}"), Part::Text("/{a b}"), Part::Text("/{-x}"), Part::Text("/{")]);
+		assert_eq!(parse("{_under-score2}"), [Part::Text(""), Part::Parameter("{_under-score2}", "_under-score2"), Part::Text("")]);
+	}
+
+	#[test]
+	fn normalizes_templates() {
+		let config = config();
+		assert_eq!(normalize_template("example.com/docs/{1}?q={query}", &config).unwrap(), "https://example.com/docs/{1}?q={query}");
+		assert_eq!(normalize_template("https://bücher.example/{1} x#{2}", &config).unwrap(), "https://xn--bcher-kva.example/{1}%20x#{2}");
+
+		assert!(matches!(normalize_template("https://example.com/", &config), Err(ShortyError::InvalidTemplate(_))));
+		assert!(matches!(normalize_template("https://{1}.example.com/", &config), Err(ShortyError::InvalidTemplate(_))));
+		assert!(matches!(normalize_template("ftp://example.com/{1}", &config), Err(ShortyError::DisallowedScheme(_))));
+	}
+
+	#[test]
+	fn expands_templates() {
+		assert_eq!(expand("https://example.com/{1}/{2}?q={q}", "/a b/c/d", "q=x%26y&z=1"), "https://example.com/a%20b/c?q=x%26y");
+		// Missing values are left empty, so is a parameter that only appears with another name.
+		assert_eq!(expand("https://example.com/{1}/{2}?q={q}", "", "Q=1"), "https://example.com//?q=");
+		// The first value of a repeated parameter is used.
+		assert_eq!(expand("https://example.com/?q={q}", "", "q=1&q=2"), "https://example.com/?q=1");
+		// Values can't add segments, a query or a fragment.
+		assert_eq!(expand("https://example.com/{1}", "/..%2F", ""), "https://example.com/..%252F");
+		assert_eq!(expand("https://example.com/{q}", "", "q=../a?b#c"), "https://example.com/..%2Fa%3Fb%23c");
+		assert_eq!(expand("https://example.com/{q}", "", "q=%C3%A4"), "https://example.com/%C3%A4");
+	}
+}
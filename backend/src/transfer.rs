@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+
+use actix_web::web::Bytes;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use url::form_urlencoded;
+use utoipa::ToSchema;
+
+use crate::domain::Domain;
+use crate::error::ShortyError;
+use crate::link::{Link, LinkConfig, LinkState};
+
+/// How many links are read from the database at once while exporting.
+pub const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// The formats links are exported to and imported from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+	/// JSON Lines, one JSON object per link and line.
+	#[default]
+	Jsonl,
+	/// Comma separated values with a header row.
+	Csv,
+}
+
+impl Format {
+	#[must_use]
+	pub fn content_type(self) -> &'static str {
+		match self {
+			Format::Jsonl => "application/x-ndjson; charset=utf-8",
+			Format::Csv => "text/csv; charset=utf-8",
+		}
+	}
+
+	#[must_use]
+	pub fn extension(self) -> &'static str {
+		match self {
+			Format::Jsonl => "jsonl",
+			Format::Csv => "csv",
+		}
+	}
+
+	/// The format of a file by its extension, if it is one of the formats.
+	#[must_use]
+	pub fn from_extension(extension: &str) -> Option<Self> {
+		match extension.to_lowercase().as_str() {
+			"jsonl" | "ndjson" => Some(Format::Jsonl),
+			"csv" => Some(Format::Csv),
+			_ => None,
+		}
+	}
+}
+
+/// What happens to an imported link whose ID is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+	/// The link isn't imported.
+	#[default]
+	Skip,
+	/// The link or alias that has the ID is replaced, links are moved to the history.
+	Overwrite,
+	/// The link is imported with a random ID instead.
+	Rename,
+}
+
+/// A link as it is exported and imported.
+/// Only `id` and `redirect_to` are required for imports. All timestamps are in milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExportedLink {
+	/// The host of the domain the link is on, empty for the default domain.
+	#[serde(default)]
+	pub domain: String,
+	pub id: String,
+	pub redirect_to: String,
+	#[serde(default)]
+	pub max_uses: i64,
+	#[serde(default)]
+	pub invocations: i64,
+	/// When the link was created, the time of the import if this is missing.
+	#[serde(default)]
+	pub created_at: Option<i64>,
+	#[serde(default)]
+	pub valid_for: i64,
+	/// The status code the link redirects with, the `default_redirect_type` if this is missing.
+	#[serde(default)]
+	pub redirect_type: Option<i64>,
+	#[serde(default)]
+	pub prefix: bool,
+	#[serde(default)]
+	pub template: bool,
+	/// The form-urlencoded query parameters added to the target.
+	#[serde(default)]
+	pub append_query: Option<String>,
+	/// The rules of the link as a JSON array.
+	#[serde(default)]
+	pub rules: Option<String>,
+	#[serde(default)]
+	pub not_before: Option<i64>,
+	#[serde(default)]
+	pub not_before_redirect_to: Option<String>,
+	#[serde(default = "status_default")]
+	pub status: LinkState,
+	#[serde(default)]
+	pub disabled_reason: Option<String>,
+	#[serde(default)]
+	pub status_changed_at: Option<i64>,
+}
+
+fn status_default() -> LinkState {
+	LinkState::Active
+}
+
+impl From<Link> for ExportedLink {
+	fn from(link: Link) -> Self {
+		Self {
+			status: link.state(),
+			domain: link.domain,
+			id: link.id,
+			redirect_to: link.redirect_to,
+			max_uses: link.max_uses,
+			invocations: link.invocations,
+			created_at: Some(link.created_at),
+			valid_for: link.valid_for,
+			redirect_type: Some(link.redirect_type),
+			prefix: link.prefix,
+			template: link.template,
+			append_query: link.append_query,
+			rules: link.rules,
+			not_before: link.not_before,
+			not_before_redirect_to: link.not_before_redirect_to,
+			disabled_reason: link.disabled_reason,
+			status_changed_at: link.status_changed_at,
+		}
+	}
+}
+
+impl ExportedLink {
+	/// The config the link is validated and created with, like any link created through the API.
+	/// Everything the config doesn't cover is restored with [`Link::restore`] afterwards.
+	fn link_config(&self) -> Result<LinkConfig, ShortyError> {
+		let rules = self.rules.as_deref()
+			.map(serde_json::from_str)
+			.transpose()
+			.map_err(|why| ShortyError::InvalidRule(why.to_string()))?;
+		let append_query = self.append_query.as_deref()
+			.map(|query| form_urlencoded::parse(query.as_bytes()).into_owned().collect::<BTreeMap<_, _>>());
+
+
+		Ok(LinkConfig {
+			custom_id: Some(self.id.clone()),
+			max_uses: Some(self.max_uses),
+			valid_for: Some(self.valid_for),
+			redirect_type: self.redirect_type,
+			prefix: Some(self.prefix),
+			template: Some(self.template),
+			append_query,
+			rules,
+			not_before: self.not_before,
+			not_before_redirect_to: self.not_before_redirect_to.clone(),
+			deduplicate: Some(false),
+			..LinkConfig::new(self.redirect_to.clone())
+		})
+	}
+}
+
+/// What happened to a link of an import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportOutcome {
+	Created,
+	/// The link replaced the link or alias that had its ID.
+	Overwritten,
+	/// The link got a random ID because its ID was taken.
+	Renamed,
+	/// The link wasn't imported because its ID was taken.
+	Skipped,
+	/// The link wasn't imported because it is invalid.
+	Failed,
+}
+
+/// The outcome of importing one link.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportResult {
+	/// The line of the input the link starts on, or its position in the array for JSON.
+	pub line: u64,
+	/// The ID of the link in the input.
+	pub id: String,
+	pub outcome: ImportOutcome,
+	/// The ID the link was imported with, if it was renamed.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub new_id: Option<String>,
+	/// Why the link was skipped or couldn't be imported.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+impl ImportResult {
+	#[must_use]
+	pub fn failed(line: u64, id: String, error: String) -> Self {
+		Self { line, id, outcome: ImportOutcome::Failed, new_id: None, error: Some(error) }
+	}
+}
+
+/// Writes the links in the format. The CSV header row is only written if `header` is set.
+///
+/// # Errors
+///
+/// Errors if a link can't be serialized.
+pub fn write(format: Format, links: &[ExportedLink], header: bool) -> Result<Bytes, ShortyError> {
+	let mut output = Vec::new();
+	match format {
+		Format::Jsonl => for link in links {
+			serde_json::to_writer(&mut output, link).map_err(std::io::Error::from)?;
+			output.push(b'\n');
+		},
+		Format::Csv => {
+			let mut writer = csv::WriterBuilder::new().has_headers(header).from_writer(&mut output);
+			for link in links {
+				writer.serialize(link)?;
+			}
+			writer.flush()?;
+		}
+	}
+
+
+	Ok(Bytes::from(output))
+}
+
+/// Reads the links of an import, with the line each of them starts on.
+/// Links that can't be read are returned as errors, so the others can still be imported.
+#[must_use]
+pub fn parse(format: Format, input: &[u8]) -> Vec<(u64, Result<ExportedLink, String>)> {
+	match format {
+		Format::Jsonl => input.split(|byte| *byte == b'\n')
+			.zip(1..)
+			.filter(|(line, _)| !line.trim_ascii().is_empty())
+			.map(|(line, number)| (number, serde_json::from_slice(line).map_err(|why| why.to_string())))
+			.collect(),
+		Format::Csv => {
+			let mut reader = csv::Reader::from_reader(input);
+			let headers = match reader.headers() {
+				Ok(headers) => headers.clone(),
+				Err(why) => return vec![(1, Err(why.to_string()))],
+			};
+
+			reader.records()
+				.map(|record| match record {
+					Ok(record) => {
+						let line = record.position().map_or(0, csv::Position::line);
+						(line, record.deserialize(Some(&headers)).map_err(|why| why.to_string()))
+					}
+					Err(why) => (why.position().map_or(0, csv::Position::line), Err(why.to_string())),
+				})
+				.collect()
+		}
+	}
+}
+
+/// Imports one link, validated like any link created through the API.
+/// Its uses, creation time and status are taken over as well.
+pub(crate) async fn import(exported: &ExportedLink, policy: ConflictPolicy, conn: &mut SqliteConnection) -> Result<(ImportOutcome, Link), ShortyError> {
+	let domain = Domain::requested(exported.domain.as_str())?;
+	let link_config = exported.link_config()?;
+	let released = policy == ConflictPolicy::Overwrite
+		&& Link::release_id(domain.name.as_str(), exported.id.as_str(), conn).await?;
+
+	let (outcome, link) = match Link::new_with_config(link_config.clone(), &domain, conn).await {
+		Ok(link) if released => (ImportOutcome::Overwritten, link),
+		Ok(link) => (ImportOutcome::Created, link),
+		Err(ShortyError::LinkConflict | ShortyError::NormalizedIdConflict(_) | ShortyError::IdRecentlyExpired(_)) if policy == ConflictPolicy::Rename => {
+			let link_config = LinkConfig { custom_id: None, ..link_config };
+			(ImportOutcome::Renamed, Link::new_with_config(link_config, &domain, conn).await?)
+		}
+		Err(why) => return Err(why),
+	};
+	Link::restore(&link, exported, conn).await?;
+
+
+	Ok((outcome, link))
+}
@@ -0,0 +1,190 @@
+use actix_web::cookie::Cookie;
+use actix_web::cookie::time::Duration;
+use actix_web::HttpRequest;
+use rand::distributions::{Distribution, WeightedIndex};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, FromRow, Pool, Sqlite, SqliteConnection};
+use utoipa::ToSchema;
+
+use crate::error::ShortyError;
+use crate::link::Link;
+
+/// The maximum number of variants a link may have, besides its own target.
+pub const MAX_VARIANTS: usize = 20;
+
+/// The cookie remembering the variant a visitor got, for links with sticky variants.
+const VARIANT_COOKIE: &str = "shorty_variant";
+
+/// How long visitors keep getting the same variant, in days.
+const VARIANT_COOKIE_DAYS: i64 = 30;
+
+/// One of the targets of a link that splits its visitors between several targets.
+/// The link's own target is the variant at position 0.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Variant {
+	#[serde(skip)]
+	pub domain: String,
+	#[serde(skip)]
+	pub link_id: String,
+	pub position: i64,
+	pub redirect_to: String,
+	/// The share of visitors getting this variant is its weight divided by the sum of all weights.
+	pub weight: i64,
+	/// How often visitors were sent to this variant.
+	pub invocations: i64,
+}
+
+/// A variant as it is requested in a [`LinkConfig`](crate::link::LinkConfig).
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[schema(example = json!({"redirect_to": "https://example.com/landing-b", "weight": 30}))]
+pub struct VariantConfig {
+	pub redirect_to: String,
+	/// The share of visitors getting this variant is its weight divided by the sum of all weights.
+	pub weight: u32,
+}
+
+impl Variant {
+	/// Stores the variants of a new link. The link's own target becomes the variant at position 0 with `weight`.
+	pub(crate) async fn create_all(link: &Link, weight: u32, variants: &[VariantConfig], conn: &mut SqliteConnection) -> Result<(), ShortyError> {
+		let own = std::iter::once((link.redirect_to.as_str(), weight));
+		let others = variants.iter().map(|variant| (variant.redirect_to.as_str(), variant.weight));
+		let mut transaction = conn.begin().await?;
+		for (position, (redirect_to, weight)) in own.chain(others).enumerate() {
+			let position = i64::try_from(position).unwrap_or(i64::MAX);
+			sqlx::query!(
+				r#"
+				INSERT INTO link_variants (domain, link_id, position, redirect_to, weight, invocations)
+				VALUES ($1, $2, $3, $4, $5, 0);
+				"#,
+				link.domain,
+				link.id,
+				position,
+				redirect_to,
+				weight,
+			)
+				.execute(&mut *transaction)
+				.await?;
+		}
+		transaction.commit().await?;
+
+
+		Ok(())
+	}
+
+	/// Retrieves the variants of the link, ordered by position.
+	pub(crate) async fn for_link(domain: &str, link_id: &str, pool: &Pool<Sqlite>) -> Result<Vec<Self>, ShortyError> {
+		let variants = sqlx::query_as!(
+			Self,
+			r#"
+			SELECT * FROM link_variants
+			WHERE domain = $1 AND link_id = $2
+			ORDER BY position;
+			"#,
+			domain,
+			link_id,
+		)
+			.fetch_all(pool)
+			.await?;
+
+
+		Ok(variants)
+	}
+
+	/// Counts a visitor sent to the variant.
+	pub(crate) async fn count_use(&self, pool: &Pool<Sqlite>) -> Result<(), ShortyError> {
+		sqlx::query!(
+			r#"
+			UPDATE link_variants
+			SET invocations = invocations + 1
+			WHERE domain = $1 AND link_id = $2 AND position = $3;
+			"#,
+			self.domain,
+			self.link_id,
+			self.position,
+		)
+			.execute(pool)
+			.await?;
+
+
+		Ok(())
+	}
+
+	/// Deletes the variants of the link, e.g. because its ID is used for a new link.
+	pub(crate) async fn delete_for(domain: &str, link_id: &str, conn: &mut SqliteConnection) -> Result<(), ShortyError> {
+		sqlx::query!(
+			r#"
+			DELETE FROM link_variants
+			WHERE domain = $1 AND link_id = $2;
+			"#,
+			domain,
+			link_id,
+		)
+			.execute(&mut *conn)
+			.await?;
+
+
+		Ok(())
+	}
+}
+
+/// Checks the weights of the variants of a new link, `weight` being the weight of its own target.
+///
+/// # Errors
+///
+/// Errors if there are too many variants or all weights are 0.
+pub fn check_weights(weight: u32, variants: &[VariantConfig]) -> Result<(), ShortyError> {
+	if variants.len() > MAX_VARIANTS {
+		return Err(ShortyError::InvalidVariants(format!("a link can have at most {MAX_VARIANTS} variants")));
+	}
+
+	if weight == 0 && variants.iter().all(|variant| variant.weight == 0) {
+		return Err(ShortyError::InvalidVariants("at least one weight must be more than 0".to_owned()));
+	}
+
+
+	Ok(())
+}
+
+/// Picks a variant by the weights. The variant at the position `sticky` is kept if it still has a weight.
+#[must_use]
+pub fn pick(variants: &[Variant], sticky: Option<i64>) -> Option<&Variant> {
+	if let Some(variant) = sticky.and_then(|position| variants.iter().find(|variant| variant.position == position && variant.weight > 0)) {
+		return Some(variant);
+	}
+
+	let weights = WeightedIndex::new(variants.iter().map(|variant| variant.weight.max(0))).ok()?;
+
+
+	variants.get(weights.sample(&mut rand::thread_rng()))
+}
+
+/// The position of the variant the visitor got before, if the cookie belongs to this link.
+#[must_use]
+pub fn sticky_position(req: &HttpRequest, link: &Link) -> Option<i64> {
+	let cookie = req.cookie(VARIANT_COOKIE)?;
+	let (created_at, position) = cookie.value().split_once('.')?;
+
+	// A cookie of a link whose ID was used again doesn't apply to the new link.
+	if created_at.parse::<i64>().ok()? != link.created_at {
+		return None;
+	}
+
+
+	position.parse().ok()
+}
+
+/// The cookie remembering the variant for the visitor. It is only sent along to the link itself.
+/// The path is the ID the way it was requested, which may be an alias or a differently normalized ID,
+/// otherwise browsers wouldn't send the cookie back on the next visit.
+#[must_use]
+pub fn sticky_cookie(req: &HttpRequest, link: &Link, variant: &Variant) -> Cookie<'static> {
+	let requested_id = req.path().trim_start_matches('/').split('/').next().unwrap_or_default();
+	let path = format!("/{requested_id}");
+
+
+	Cookie::build(VARIANT_COOKIE, format!("{}.{}", link.created_at, variant.position))
+		.path(path)
+		.max_age(Duration::days(VARIANT_COOKIE_DAYS))
+		.http_only(true)
+		.finish()
+}
@@ -118,6 +118,7 @@ pub struct LinkFormRefs {
     pub custom_id_input: NodeRef,
     pub expiration_input: NodeRef,
     pub expiration_type: NodeRef,
+    pub start_input: NodeRef,
 }
 
 #[derive(Clone, Debug)]
@@ -227,7 +228,7 @@ impl Component for LinkForm {
         let maxlength_link = self.server_config.as_ref().map(|c| AttrValue::from(format!("{}", c.max_custom_id_length)));
         let max_uses = self.server_config.as_ref().map(|c| AttrValue::from(format!("{}", c.default_max_uses)));
 
-        let ids = [generate_id(), generate_id(), generate_id()];
+        let ids = [generate_id(), generate_id(), generate_id(), generate_id()];
 
         // TODO remove code duplication
         html! {
@@ -249,6 +250,10 @@ impl Component for LinkForm {
                             <ExpirationInput id={ ids[2].clone() } toggle_ref={ self.refs.expiration_type.clone() } input_ref={ self.refs.expiration_input.clone() }/>
                         </div>
                     </div>
+                    <div class={ CONTAINER.as_classes() }>
+                        <label class={ LABEL.as_classes() } for={ ids[3].clone() }>{ "Available from" }</label>
+                        <input id={ ids[3].clone() } class={ TEXT_INPUT.as_classes() } ref={ self.refs.start_input.clone() } type="datetime-local"/>
+                    </div>
                 </AdvancedMode>
             </>
         }
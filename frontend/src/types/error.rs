@@ -17,6 +17,8 @@ pub enum FormError {
     // TODO make error message better by including set date or duration
     #[error("Expiration is in the past which would invalidate the link instantly upon creation")]
     NegativeExpiration { seconds: i64 },
+    #[error("{start} is not a valid start date. Please input a valid one.")]
+    InvalidStart { start: String },
     #[error("The number of uses is negative and would therefore invalidate the link instantly upon creation")]
     NegativeMaxUses { max_uses: i64 },
 }
@@ -1,10 +1,11 @@
 use nonempty_collections::NEVec;
 use serde::Serialize;
 use time::{
-    format_description::well_known::Iso8601,
-    macros::time,
+    format_description::{well_known::Iso8601, FormatItem},
+    macros::{format_description, time},
     Date,
     OffsetDateTime,
+    PrimitiveDateTime,
     UtcOffset,
 };
 use validated::{
@@ -20,8 +21,12 @@ use crate::{
         error::FormError,
         ServerConfig,
     },
+    util::try_get_local_offset,
 };
 
+/// The format of the value of a `datetime-local` input
+const START_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]T[hour]:[minute]");
+
 #[derive(Debug, Serialize, Clone)]
 pub struct LinkConfig {
     pub link: String,
@@ -32,6 +37,9 @@ pub struct LinkConfig {
     // could be u32 if https://github.com/flamion/shorty/issues/51 is resolved
     #[serde(skip_serializing_if = "Option::is_none")]
     pub valid_for: Option<i64>,
+    // milliseconds since the unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<i64>,
 }
 
 impl LinkConfig {
@@ -56,6 +64,7 @@ impl LinkConfig {
         let mut id = Ok(None);
         let mut max_uses = Ok(None);
         let mut valid_for = Ok(None);
+        let mut not_before = Ok(None);
 
         if input.checked() {
             id = Self::parse_id(refs, server_config)
@@ -64,7 +73,10 @@ impl LinkConfig {
             max_uses = Self::parse_max_uses(refs)
                 .ok()
                 .map_err(|e| errors.extend(e.into_iter()));
-            valid_for = Self::parse_valid_for(refs)
+            not_before = Self::parse_not_before(refs)
+                .ok()
+                .map_err(|e| errors.extend(e.into_iter()));
+            valid_for = Self::parse_valid_for(refs, not_before.clone().ok().flatten())
                 .ok()
                 .map_err(|e| errors.extend(e.into_iter()));
         }
@@ -75,6 +87,7 @@ impl LinkConfig {
                 id: id.unwrap(),
                 max_uses: max_uses.unwrap(),
                 valid_for: valid_for.unwrap(),
+                not_before: not_before.unwrap(),
             })
         } else {
             Fail(NEVec::from_vec(errors).unwrap())
@@ -176,7 +189,39 @@ impl LinkConfig {
         Good(Some(value))
     }
 
-    fn parse_valid_for(refs: &LinkFormRefs) -> Validated<Option<i64>, FormError> {
+    /// The start in milliseconds, the input is in local time.
+    fn parse_not_before(refs: &LinkFormRefs) -> Validated<Option<i64>, FormError> {
+        let input = refs.start_input.cast::<HtmlInputElement>().expect(&format!(
+            "Expected {:?} to be an HtmlInputElement",
+            refs.start_input
+        ));
+
+        let value = input.value();
+
+        if value.is_empty() {
+            return Good(None);
+        }
+
+        let Ok(date_time) = PrimitiveDateTime::parse(&value, START_FORMAT) else {
+            return Validated::fail(FormError::InvalidStart { start: value });
+        };
+
+        // TODO dispatch a warning
+        let offset = try_get_local_offset().unwrap_or(UtcOffset::UTC);
+        let start = date_time.assume_offset(offset);
+
+        // a start in the past means the link is available right away
+        if start <= OffsetDateTime::now_utc() {
+            return Good(None);
+        }
+
+        Good(Some(start.unix_timestamp() * 1000))
+    }
+
+    fn parse_valid_for(
+        refs: &LinkFormRefs,
+        not_before: Option<i64>,
+    ) -> Validated<Option<i64>, FormError> {
         let input = refs
             .expiration_type
             .cast::<HtmlInputElement>()
@@ -186,7 +231,7 @@ impl LinkConfig {
             ));
 
         if ExpirationType::Date == ExpirationType::from(input.checked()) {
-            Self::parse_date(refs)
+            Self::parse_date(refs, not_before)
         } else {
             Self::parse_duration(refs)
         }
@@ -219,7 +264,8 @@ impl LinkConfig {
         Good(Some(seconds))
     }
 
-    fn parse_date(refs: &LinkFormRefs) -> Validated<Option<i64>, FormError> {
+    /// The backend counts `valid_for` from the start of the link, so the difference is taken from there.
+    fn parse_date(refs: &LinkFormRefs, not_before: Option<i64>) -> Validated<Option<i64>, FormError> {
         let input = refs
             .expiration_input
             .cast::<HtmlInputElement>()
@@ -239,11 +285,10 @@ impl LinkConfig {
 
         let date_time = date.with_time(time!(00:00)).assume_offset(UtcOffset::UTC);
 
-        let local_now = OffsetDateTime::now_utc();
+        let start = not_before.unwrap_or_else(|| OffsetDateTime::now_utc().unix_timestamp() * 1000);
 
-        let mut difference = date_time.unix_timestamp() - local_now.unix_timestamp();
         // because the timestamp is needed in milliseconds
-        difference *= 1000;
+        let difference = date_time.unix_timestamp() * 1000 - start;
 
         if difference < 0 {
             return Validated::fail(FormError::NegativeExpiration {